@@ -0,0 +1,403 @@
+use std::{fs, ops::Range};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::day_three::Matrix;
+
+pub trait CellState: Copy + Eq {
+    fn from_char(chr: char) -> Option<Self>;
+
+    fn to_char(&self) -> char;
+}
+
+impl CellState for char {
+    fn from_char(chr: char) -> Option<char> {
+        Some(chr)
+    }
+
+    fn to_char(&self) -> char {
+        *self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    Moore(usize),
+    VonNeumann(usize),
+    Line(usize)
+}
+
+impl Neighborhood {
+    // Offsets of the neighbors, without the cell itself. For a line the offsets go
+    // from left to right, so they can be matched against a window pattern.
+    fn get_offsets(&self) -> Vec<(isize, isize)> {
+        let mut offsets: Vec<(isize, isize)> = Vec::new();
+        match *self {
+            Neighborhood::Moore(radius) | Neighborhood::VonNeumann(radius) => {
+                let radius = radius as isize;
+                for row_offset in -radius..=radius {
+                    for col_offset in -radius..=radius {
+                        let is_inside = match self {
+                            Neighborhood::VonNeumann(_) => row_offset.abs() + col_offset.abs() <= radius,
+                            _ => true,
+                        };
+                        if is_inside && (row_offset, col_offset) != (0, 0) {
+                            offsets.push((row_offset, col_offset));
+                        }
+                    }
+                }
+            },
+            Neighborhood::Line(radius) => {
+                let radius = radius as isize;
+                for col_offset in -radius..=radius {
+                    if col_offset != 0 {
+                        offsets.push((0, col_offset));
+                    }
+                }
+            },
+        }
+        offsets
+    }
+
+    fn get_radius(&self) -> usize {
+        match *self {
+            Neighborhood::Moore(radius) | Neighborhood::VonNeumann(radius) | Neighborhood::Line(radius) => radius,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater
+}
+
+impl Comparison {
+    fn from_string(string: &str) -> Option<Comparison> {
+        match string {
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            ">" => Some(Comparison::Greater),
+            _ => None,
+        }
+    }
+
+    fn is_satisfied(&self, first: usize, second: usize) -> bool {
+        match self {
+            Comparison::Less => first < second,
+            Comparison::LessOrEqual => first <= second,
+            Comparison::Equal => first == second,
+            Comparison::NotEqual => first != second,
+            Comparison::GreaterOrEqual => first >= second,
+            Comparison::Greater => first > second,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition<T> {
+    states: Vec<T>,
+    comparison: Comparison,
+    value: usize
+}
+
+impl<T: CellState> Condition<T> {
+    fn is_satisfied(&self, neighbors: &[T]) -> bool {
+        let count = neighbors.iter()
+            .filter(|neighbor| self.states.contains(neighbor))
+            .count();
+        self.comparison.is_satisfied(count, self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule<T> {
+    Transition { from: Option<T>, to: T, conditions: Vec<Condition<T>> },
+    Window { pattern: Vec<T>, to: T }
+}
+
+impl<T: CellState> Rule<T> {
+    fn get_next_state(&self, state: T, neighbors: &[T]) -> Option<T> {
+        match self {
+            Rule::Transition { from, to, conditions } => {
+                let is_matched = from.is_none_or(|from| from == state) &&
+                    conditions.iter().all(|condition| condition.is_satisfied(neighbors));
+                if is_matched { Some(*to) } else { None }
+            },
+            Rule::Window { pattern, to } => {
+                let (left, right) = neighbors.split_at(neighbors.len() / 2);
+                let is_matched = pattern[..left.len()] == *left && pattern[left.len()] == state &&
+                    pattern[(left.len() + 1)..] == *right;
+                if is_matched { Some(*to) } else { None }
+            },
+        }
+    }
+}
+
+// A set of rules in the following textual form, one statement per line:
+//
+//     neighborhood moore 1
+//     background .
+//     default keep
+//     . -> | if count(|) >= 3
+//     # -> # if count(#) >= 1 and count(|) >= 1
+//     # -> .
+//
+// The neighborhood is `moore`, `von-neumann` or `line` followed by its radius and
+// cells outside the grid are in the background state. A rule starts with the state
+// it applies to, `*` for any state or, for a line, a window pattern covering the
+// whole neighborhood; `=>` may be used instead of `->`. The first matching rule
+// decides the next state. A cell no rule matches goes to the `default` state, or
+// keeps its own with `default keep`; there is no implicit default, since rule
+// lists differ in which one they leave out. Lines starting with `//` are comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet<T> {
+    pub neighborhood: Neighborhood,
+    pub background: T,
+    // None keeps the current state.
+    default: Option<T>,
+    rules: Vec<Rule<T>>,
+    offsets: Vec<(isize, isize)>
+}
+
+fn parse_state<T: CellState>(string: &str) -> Result<T, String> {
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+        (Some(chr), None) => T::from_char(chr).ok_or(format!("unknown state '{}'", chr)),
+        _ => Err(format!("expected a single state, found \"{}\"", string)),
+    }
+}
+
+fn parse_states<T: CellState>(string: &str) -> Result<Vec<T>, String> {
+    string.chars()
+        .map(|chr| T::from_char(chr).ok_or(format!("unknown state '{}'", chr)))
+        .collect()
+}
+
+fn parse_neighborhood(string: &str) -> Result<Neighborhood, String> {
+    let words: Vec<&str> = string.split_whitespace().collect();
+    if words.len() != 2 {
+        return Err(String::from("expected a neighborhood kind and a radius"));
+    }
+    let radius = words[1].parse::<usize>()
+        .map_err(|_| format!("invalid radius \"{}\"", words[1]))?;
+    match words[0] {
+        "moore" => Ok(Neighborhood::Moore(radius)),
+        "von-neumann" => Ok(Neighborhood::VonNeumann(radius)),
+        "line" => Ok(Neighborhood::Line(radius)),
+        kind => Err(format!("unknown neighborhood \"{}\"", kind)),
+    }
+}
+
+fn parse_condition<T: CellState>(string: &str) -> Result<Condition<T>, String> {
+    lazy_static! {
+        static ref REGEX: Regex = Regex::new(r"^count\((.+)\)\s*(<=|>=|==|!=|<|>)\s*(\d+)$").unwrap();
+    }
+    let captures = REGEX.captures(string.trim())
+        .ok_or(format!("expected \"count(<states>) <comparison> <number>\", found \"{}\"", string.trim()))?;
+    let states = parse_states(&captures[1])?;
+    let comparison = Comparison::from_string(&captures[2]).unwrap();
+    let value = captures[3].parse::<usize>()
+        .map_err(|_| format!("invalid count \"{}\"", &captures[3]))?;
+    Ok(Condition { states, comparison, value })
+}
+
+fn parse_rule<T: CellState>(string: &str, neighborhood: Neighborhood) -> Result<Rule<T>, String> {
+    lazy_static! {
+        static ref REGEX: Regex = Regex::new(r"^(\S+)\s*(?:->|=>)\s*(\S+)(?:\s+if\s+(.+))?$").unwrap();
+    }
+    let captures = REGEX.captures(string)
+        .ok_or(format!("expected \"<state> -> <state>\", found \"{}\"", string))?;
+    let to = parse_state(&captures[2])?;
+    let from = &captures[1];
+    if from.chars().count() == 1 {
+        let conditions = match captures.get(3) {
+            Some(conditions) => conditions.as_str()
+                .split(" and ")
+                .map(parse_condition)
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
+        let from = if from == "*" { None } else { Some(parse_state(from)?) };
+        Ok(Rule::Transition { from, to, conditions })
+    } else if let Neighborhood::Line(radius) = neighborhood {
+        let pattern: Vec<T> = parse_states(from)?;
+        if pattern.len() != 2 * radius + 1 {
+            Err(format!("window pattern \"{}\" must have {} states", from, 2 * radius + 1))
+        } else if captures.get(3).is_some() {
+            Err(String::from("window patterns can not have conditions"))
+        } else {
+            Ok(Rule::Window { pattern, to })
+        }
+    } else {
+        Err(String::from("window patterns need a line neighborhood"))
+    }
+}
+
+impl<T: CellState> RuleSet<T> {
+    pub fn from_string(string: &str) -> Result<RuleSet<T>, String> {
+        let mut neighborhood: Option<Neighborhood> = None;
+        let mut background: Option<T> = None;
+        let mut default: Option<Option<T>> = None;
+        let mut rules: Vec<Rule<T>> = Vec::new();
+        for (index, line) in string.lines().enumerate() {
+            let line = line.trim();
+            let result = if line.is_empty() || line.starts_with("//") {
+                Ok(())
+            } else if let Some(rest) = line.strip_prefix("neighborhood ") {
+                parse_neighborhood(rest).map(|parsed| neighborhood = Some(parsed))
+            } else if let Some(rest) = line.strip_prefix("background ") {
+                parse_state(rest.trim()).map(|parsed| background = Some(parsed))
+            } else if let Some(rest) = line.strip_prefix("default ") {
+                let rest = rest.trim();
+                if rest == "keep" {
+                    default = Some(None);
+                    Ok(())
+                } else {
+                    parse_state(rest).map(|parsed| default = Some(Some(parsed)))
+                }
+            } else if let Some(neighborhood) = neighborhood {
+                parse_rule(line, neighborhood).map(|rule| rules.push(rule))
+            } else {
+                Err(String::from("the neighborhood must be declared before the rules"))
+            };
+            result.map_err(|message| format!("line {}: {}", index + 1, message))?;
+        }
+        let neighborhood = neighborhood.ok_or("missing neighborhood declaration")?;
+        let background = background.ok_or("missing background declaration")?;
+        let default = default.ok_or("missing default declaration")?;
+        Ok(
+            RuleSet {
+                neighborhood,
+                background,
+                default,
+                rules,
+                offsets: neighborhood.get_offsets()
+            }
+        )
+    }
+
+    pub fn from_file(path: &str) -> Result<RuleSet<T>, String> {
+        let string = fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        RuleSet::from_string(&string)
+            .map_err(|message| format!("{}: {}", path, message))
+    }
+
+    fn get_next_state(&self, state: T, neighbors: &[T]) -> T {
+        self.rules.iter()
+            .find_map(|rule| rule.get_next_state(state, neighbors))
+            .unwrap_or(self.default.unwrap_or(state))
+    }
+
+    // Next state of the middle cell of a window spanning a line neighborhood.
+    pub fn get_next_window_state(&self, window: &[T]) -> T {
+        let middle = window.len() / 2;
+        let neighbors: Vec<T> = window[..middle].iter()
+            .chain(window[(middle + 1)..].iter())
+            .copied()
+            .collect();
+        self.get_next_state(window[middle], &neighbors)
+    }
+
+    fn get_matrix_state(&self, matrix: &Matrix<T>, row: isize, col: isize) -> T {
+        if row >= 0 && col >= 0 && (row as usize) < matrix.rows && (col as usize) < matrix.cols {
+            *matrix.get_ref(row as usize, col as usize)
+        } else {
+            self.background
+        }
+    }
+
+    // Computes the next state of the given rows into `next_rows`, which holds one
+    // row for each of them. Only `matrix` is read, so disjoint row ranges can be
+    // computed at the same time.
+    pub fn fill_next_rows(&self, matrix: &Matrix<T>, rows: Range<usize>, next_rows: &mut [Vec<T>]) {
+        let mut neighbors: Vec<T> = Vec::with_capacity(self.offsets.len());
+        for (row, next_row) in rows.zip(next_rows.iter_mut()) {
+            for (col, next_state) in next_row.iter_mut().enumerate() {
+                neighbors.clear();
+                for &(row_offset, col_offset) in &self.offsets {
+                    neighbors.push(self.get_matrix_state(matrix, row as isize + row_offset, col as isize + col_offset));
+                }
+                *next_state = self.get_next_state(*matrix.get_ref(row, col), &neighbors);
+            }
+        }
+    }
+
+    pub fn get_next_matrix(&self, matrix: &Matrix<T>) -> Matrix<T> {
+        let mut next_matrix = matrix.clone();
+        self.fill_next_rows(matrix, 0..matrix.rows, &mut next_matrix.data);
+        next_matrix
+    }
+
+    // The line is unbounded: it grows by the neighborhood radius on both sides and
+    // then drops the background cells at its ends.
+    pub fn get_next_line(&self, line: &Line<T>) -> Line<T> {
+        let radius = self.neighborhood.get_radius() as i64;
+        let first = line.offset - radius;
+        let last = line.offset + line.cells.len() as i64 + radius;
+        let mut neighbors: Vec<T> = Vec::with_capacity(self.offsets.len());
+        let mut cells: Vec<T> = Vec::with_capacity((last - first) as usize);
+        for position in first..last {
+            neighbors.clear();
+            for &(_, col_offset) in &self.offsets {
+                neighbors.push(line.get(position + col_offset as i64, self.background));
+            }
+            cells.push(self.get_next_state(line.get(position, self.background), &neighbors));
+        }
+        Line::new(cells, first, self.background)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Line<T> {
+    pub cells: Vec<T>,
+    pub offset: i64
+}
+
+impl<T: CellState> Line<T> {
+    pub fn new(cells: Vec<T>, offset: i64, background: T) -> Line<T> {
+        let first = cells.iter().position(|&cell| cell != background);
+        match first {
+            Some(first) => {
+                let last = cells.iter().rposition(|&cell| cell != background).unwrap();
+                Line {
+                    cells: cells[first..=last].to_vec(),
+                    offset: offset + first as i64
+                }
+            },
+            None => Line { cells: vec![], offset: 0 },
+        }
+    }
+
+    pub fn from_string(string: &str, background: T) -> Option<Line<T>> {
+        let cells = string.chars()
+            .map(T::from_char)
+            .collect::<Option<Vec<T>>>()?;
+        Some(Line::new(cells, 0, background))
+    }
+
+    pub fn get(&self, position: i64, background: T) -> T {
+        if position < self.offset || position >= self.offset + self.cells.len() as i64 {
+            background
+        } else {
+            self.cells[(position - self.offset) as usize]
+        }
+    }
+
+    pub fn get_positions(&self, state: T) -> Vec<i64> {
+        self.cells.iter()
+            .enumerate()
+            .filter(|&(_, &cell)| cell == state)
+            .map(|(index, _)| self.offset + index as i64)
+            .collect()
+    }
+}
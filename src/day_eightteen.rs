@@ -1,8 +1,7 @@
-use crate::{day_three::{Matrix, MatrixRange}, utils::read_matrix};
-use core::f64;
-use std::{fmt, sync::mpsc::{Receiver, Sender, channel}, thread::{self, JoinHandle, current}};
+use crate::{automaton::{CellState, RuleSet}, day_three::Matrix, utils::{get_state_after_steps, read_matrix}};
+use std::{fmt, ops::Range, slice, sync::{Barrier, atomic::{AtomicUsize, Ordering}}, thread, time::Instant};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Acre {
     Open,
     Tree,
@@ -12,14 +11,15 @@ enum Acre {
 const OPEN_ACRE_CHAR: char = '.';
 const TREE_ACRE_CHAR: char = '|';
 const LUMBERYARD_ACRE_CHAR: char = '#';
+const ROWS_PER_TASK: usize = 4;
 
-impl Acre {
-    fn from_char(chr: char) -> Acre {
+impl CellState for Acre {
+    fn from_char(chr: char) -> Option<Acre> {
         match chr {
-            OPEN_ACRE_CHAR => Acre::Open,
-            TREE_ACRE_CHAR => Acre::Tree,
-            LUMBERYARD_ACRE_CHAR => Acre::Lumberyard,
-            _ => Acre::Open,
+            OPEN_ACRE_CHAR => Some(Acre::Open),
+            TREE_ACRE_CHAR => Some(Acre::Tree),
+            LUMBERYARD_ACRE_CHAR => Some(Acre::Lumberyard),
+            _ => None,
         }
     }
 
@@ -45,215 +45,121 @@ impl fmt::Display for Matrix<Acre> {
     }
 }
 
-const OFFSETS: &[(isize, isize); 8] = &[
-    (-1, 0),
-    (-1, 1),
-    (0, 1),
-    (1, 1),
-    (1, 0),
-    (1, -1),
-    (0, -1),
-    (-1, -1)
-];
-
-fn get_adjacent_acres_info(matrix: &Matrix<Acre>, row: usize, col: usize) -> (usize, usize, usize) {
-    let mut num_open_acres: usize = 0;
-    let mut num_tree_acres: usize = 0;
-    let mut num_lumberyard_acres: usize = 0;
-    for &(row_offset, col_offset) in OFFSETS {
-        let offseted_row = (row as isize) + row_offset;
-        let offseted_col = (col as isize) + col_offset;
-        if offseted_row >= 0 && offseted_row < (matrix.rows as isize) &&
-            offseted_col >= 0 && offseted_col < (matrix.cols as isize) {
-                match matrix.get(offseted_row as usize, offseted_col as usize) {
-                    Acre::Open => { num_open_acres += 1 },
-                    Acre::Tree => { num_tree_acres += 1 },
-                    Acre::Lumberyard => { num_lumberyard_acres += 1 },
-                }
-        }
-    }
-    (num_open_acres, num_tree_acres, num_lumberyard_acres)
-}
-
-fn get_updated_matrix(matrix: &Matrix<Acre>) -> Matrix<Acre> {
-    let mut updated_matrix = matrix.clone();
-    for row in 0..matrix.rows {
-        for col in 0..matrix.cols {
-            let (_, num_tree_acres, num_lumberyard_acres) = get_adjacent_acres_info(
-                matrix, 
-                row, 
-                col
-            );
-            match matrix.get(row, col) {
-                Acre::Open => {
-                    if num_tree_acres >= 3 {
-                        updated_matrix.set(row, col, Acre::Tree);
-                    }
-                },
-                Acre::Tree => {
-                    if num_lumberyard_acres >= 3 {
-                        updated_matrix.set(row, col, Acre::Lumberyard);
-                    }
-                },
-                Acre::Lumberyard => {
-                    if !(num_lumberyard_acres >= 1 && num_tree_acres >= 1) {
-                        updated_matrix.set(row, col, Acre::Open);
-                    }
-                },
-            }
-        }
-    }
-    updated_matrix
+fn get_rule_set() -> RuleSet<Acre> {
+    RuleSet::from_file("day_eightteen_rules.txt")
+        .unwrap_or_else(|message| panic!("Invalid acre rules: {}", message))
 }
 
 fn get_acres_matrix(char_matrix: &Matrix<char>) -> Matrix<Acre> {
     char_matrix.map(Acre::Open, |&chr| {
-        Acre::from_char(chr)
+        Acre::from_char(chr).unwrap_or(Acre::Open)
     })
 }
 
-fn get_acres_matrix_after_iterations(matrix: &Matrix<Acre>, num_iterations: usize) -> Matrix<Acre> {
+fn get_acres_matrix_after_iterations(matrix: &Matrix<Acre>, rule_set: &RuleSet<Acre>, num_iterations: usize) -> Matrix<Acre> {
     let mut current_matrix = matrix.clone();
     for _ in 0..num_iterations {
-        current_matrix = get_updated_matrix(&current_matrix);
+        current_matrix = rule_set.get_next_matrix(&current_matrix);
     }
     current_matrix
 }
 
-fn get_acres_matrix_after_large_iterations(matrix: &Matrix<Acre>, num_iterations: usize) -> Matrix<Acre> {
-    let mut current_matrix = matrix.clone();
-    let mut matrices: Vec<Matrix<Acre>> = Vec::new();
-    matrices.push(current_matrix.clone());
-    for _ in 0..num_iterations {
-        current_matrix = get_updated_matrix(&current_matrix);
-        if let Some(index) = matrices.iter().position(|mat| *mat == current_matrix) {
-            let cycle_length = matrices.len() - index;
-            let matrix_index = (num_iterations - index) % cycle_length + index;
-            return matrices[matrix_index].clone();
-        } else {
-            matrices.push(current_matrix.clone());
-        }
-    }
-    current_matrix
+fn get_acres_matrix_after_large_iterations(matrix: &Matrix<Acre>, rule_set: &RuleSet<Acre>, num_iterations: usize) -> Matrix<Acre> {
+    get_state_after_steps(matrix.clone(), num_iterations, |current_matrix| rule_set.get_next_matrix(current_matrix))
 }
 
-fn get_matrix_ranges(matrix_range: &MatrixRange, num: usize) -> Vec<MatrixRange> {
-    let mut current_matrix_ranges: Vec<MatrixRange> = Vec::new();
-    current_matrix_ranges.push(matrix_range.clone());
-    for index in 1..=num {
-        let mut updated_matrix_ranges: Vec<MatrixRange> = Vec::new();
-        for current_matrix_range in &current_matrix_ranges {
-            let (first_split, second_split) = if index % 2 == 1 {
-                current_matrix_range.half_horizontal()
-            } else {
-                current_matrix_range.half_vertical()
-            };
-            updated_matrix_ranges.push(first_split);
-            updated_matrix_ranges.push(second_split);
-        }
-        current_matrix_ranges = updated_matrix_ranges;
-    }
-    current_matrix_ranges
+// One of the two matrices the workers alternate between. A generation reads the
+// matrix the previous one wrote and writes the other one, and every row of it is
+// handed to a single worker, so no row is written by two threads or read while it
+// is written.
+struct SharedMatrix<T> {
+    matrix: *const Matrix<T>,
+    rows: *mut Vec<T>
 }
 
-fn get_updated_matrix_in_range(matrix: &Matrix<Acre>, matrix_range: &MatrixRange) -> Matrix<Acre> {
-    let mut updated_matrix: Matrix<Acre> = Matrix::new(matrix_range.rows(), matrix_range.cols(), Acre::Open);
-    for row in matrix_range.row_range.clone() {
-        for col in matrix_range.col_range.clone() {
-            updated_matrix.set(
-                row - matrix_range.first_row(),
-                col - matrix_range.first_col(),
-                matrix.get(row, col)
-            );
-        }
-    }
-    for row in matrix_range.row_range.clone() {
-        for col in matrix_range.col_range.clone() {
-            let (_, num_tree_acres, num_lumberyard_acres) = get_adjacent_acres_info(
-                matrix, 
-                row, 
-                col
-            );
-            match matrix.get(row, col) {
-                Acre::Open => {
-                    if num_tree_acres >= 3 {
-                        updated_matrix.set(row - matrix_range.first_row(), col - matrix_range.first_col(), Acre::Tree);
-                    }
-                },
-                Acre::Tree => {
-                    if num_lumberyard_acres >= 3 {
-                        updated_matrix.set(row - matrix_range.first_row(), col - matrix_range.first_col(), Acre::Lumberyard);
-                    }
-                },
-                Acre::Lumberyard => {
-                    if !(num_lumberyard_acres >= 1 && num_tree_acres >= 1) {
-                        updated_matrix.set(row - matrix_range.first_row(), col - matrix_range.first_col(), Acre::Open);
-                    }
-                },
-            }
-        }
-    }
-    updated_matrix
-}
+unsafe impl<T: Send + Sync> Sync for SharedMatrix<T> {}
 
-fn replace_matrix(matrix: &mut Matrix<Acre>, matrix_range: &MatrixRange, partial_matrix: &Matrix<Acre>) {
-    for row in matrix_range.row_range.clone() {
-        for col in matrix_range.col_range.clone() {
-            let value = partial_matrix.get(row - matrix_range.first_row(), col - matrix_range.first_col());
-            matrix.set(row, col, value);
+impl<T> SharedMatrix<T> {
+    fn new(matrix: &mut Matrix<T>) -> SharedMatrix<T> {
+        SharedMatrix {
+            rows: matrix.data.as_mut_ptr(),
+            matrix
         }
     }
-}
 
-fn get_updated_matrix_with_threads(matrix: &Matrix<Acre>, num_threads: usize) -> Matrix<Acre> {
-    let mut updated_matrix = matrix.clone();
-    let num = ((num_threads as f64).ln() / (2.0f64).ln()).floor() as usize;
-    let mut matrix_ranges = get_matrix_ranges(&matrix.get_range(), num);
-    let (aux_tx, rx): (Sender<(MatrixRange, Matrix<Acre>)>, Receiver<(MatrixRange, Matrix<Acre>)>) = 
-        channel();
-    let mut threads_handle = Vec::new();
-    for _ in 0..num_threads {
-        let tx = aux_tx.clone();
-        let original_matrix = matrix.clone();
-        let matrix_range = matrix_ranges.remove(0);
-        let join_handle = thread::spawn(move || {
-            let updated_matrix = get_updated_matrix_in_range(&original_matrix, &matrix_range);
-            tx.send((matrix_range, updated_matrix)).unwrap();
-        });
-        threads_handle.push(join_handle);
+    // Safe as long as no worker writes this matrix during the generation.
+    unsafe fn get(&self) -> &Matrix<T> {
+        &*self.matrix
     }
-    for _ in 0..num_threads {
-        let (matrix_range, partial_matrix) = rx.recv().unwrap();
-        replace_matrix(&mut updated_matrix, &matrix_range, &partial_matrix);
-    }
-    for handle in threads_handle {
-        handle.join().unwrap();
+
+    // Safe as long as no other worker reads or writes these rows during the
+    // generation.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn get_rows_mut(&self, rows: Range<usize>) -> &mut [Vec<T>] {
+        slice::from_raw_parts_mut(self.rows.add(rows.start), rows.len())
     }
-    updated_matrix
 }
 
-fn get_acres_matrix_after_iterations_with_threads(matrix: &Matrix<Acre>, num_iterations: usize, num_threads: usize) -> Matrix<Acre> {
-    let mut current_matrix = matrix.clone();
-    for _ in 0..num_iterations {
-        current_matrix = get_updated_matrix_with_threads(&current_matrix, num_threads);
-    }
-    current_matrix
+// The workers are spawned once per run, the calling thread included, and go through
+// the generations together, a barrier separating one from the next. Within a
+// generation rows are handed out a few at a time from a shared counter, so a worker
+// that is done early keeps taking rows instead of idling while others finish a
+// fixed band. Consecutive generations use alternate counters, so the one of a
+// finished generation can be reset while workers already take rows in the next.
+fn get_acres_matrix_after_iterations_with_threads(matrix: &Matrix<Acre>, rule_set: &RuleSet<Acre>, num_iterations: usize, num_threads: usize) -> Matrix<Acre> {
+    let num_threads = num_threads.max(1);
+    let mut buffers = [matrix.clone(), matrix.clone()];
+    let [first_buffer, second_buffer] = &mut buffers;
+    let shared_buffers = [SharedMatrix::new(first_buffer), SharedMatrix::new(second_buffer)];
+    let next_task_rows = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    let barrier = Barrier::new(num_threads);
+    let run_worker = || {
+        for generation in 0..num_iterations {
+            let current_matrix = &shared_buffers[generation % 2];
+            let next_matrix = &shared_buffers[(generation + 1) % 2];
+            let next_task_row = &next_task_rows[generation % 2];
+            loop {
+                let start = next_task_row.fetch_add(ROWS_PER_TASK, Ordering::Relaxed);
+                if start >= matrix.rows {
+                    break;
+                }
+                let end = (start + ROWS_PER_TASK).min(matrix.rows);
+                // The barrier keeps the matrix being read out of the previous
+                // generation's writes, and the counter gives these rows to this
+                // worker only.
+                unsafe {
+                    rule_set.fill_next_rows(current_matrix.get(), start..end, next_matrix.get_rows_mut(start..end));
+                }
+            }
+            if barrier.wait().is_leader() {
+                next_task_row.store(0, Ordering::Relaxed);
+            }
+        }
+    };
+    thread::scope(|scope| {
+        for _ in 1..num_threads {
+            scope.spawn(run_worker);
+        }
+        run_worker();
+    });
+    let [first_buffer, second_buffer] = buffers;
+    if num_iterations.is_multiple_of(2) { first_buffer } else { second_buffer }
 }
 
 pub fn solve_part_one(num_iterations: usize) {
     let char_matrix = read_matrix("day_eightteen.txt");
     let matrix = get_acres_matrix(&char_matrix);
-    let final_matrix = get_acres_matrix_after_iterations(&matrix, num_iterations);
+    let final_matrix = get_acres_matrix_after_iterations(&matrix, &get_rule_set(), num_iterations);
     let num_tree_acres = final_matrix.count(&Acre::Tree);
     let num_lumberyard_acres = final_matrix.count(&Acre::Lumberyard);
     let answer = num_tree_acres * num_lumberyard_acres;
     println!("{}", answer);
 }
 
-pub fn solve_part_one_with_channels(num_iterations: usize, num_threads: usize) {
+pub fn solve_part_one_with_threads(num_iterations: usize, num_threads: usize) {
     let char_matrix = read_matrix("day_eightteen.txt");
     let matrix = get_acres_matrix(&char_matrix);
-    let final_matrix = get_acres_matrix_after_iterations_with_threads(&matrix, num_iterations, num_threads);
+    let final_matrix = get_acres_matrix_after_iterations_with_threads(&matrix, &get_rule_set(), num_iterations, num_threads);
     let num_tree_acres = final_matrix.count(&Acre::Tree);
     let num_lumberyard_acres = final_matrix.count(&Acre::Lumberyard);
     let answer = num_tree_acres * num_lumberyard_acres;
@@ -263,9 +169,25 @@ pub fn solve_part_one_with_channels(num_iterations: usize, num_threads: usize) {
 pub fn solve_part_two(num_iterations: usize) {
     let char_matrix = read_matrix("day_eightteen.txt");
     let matrix = get_acres_matrix(&char_matrix);
-    let final_matrix = get_acres_matrix_after_large_iterations(&matrix, num_iterations);
+    let final_matrix = get_acres_matrix_after_large_iterations(&matrix, &get_rule_set(), num_iterations);
     let num_tree_acres = final_matrix.count(&Acre::Tree);
     let num_lumberyard_acres = final_matrix.count(&Acre::Lumberyard);
     let answer = num_tree_acres * num_lumberyard_acres;
     println!("{}", answer);
+}
+
+pub fn benchmark_threads(num_iterations: usize, max_threads: usize) {
+    let char_matrix = read_matrix("day_eightteen.txt");
+    let matrix = get_acres_matrix(&char_matrix);
+    let rule_set = get_rule_set();
+    let start = Instant::now();
+    let sequential_matrix = get_acres_matrix_after_iterations(&matrix, &rule_set, num_iterations);
+    println!("sequential: {:?}", start.elapsed());
+    for num_threads in 1..=max_threads {
+        let start = Instant::now();
+        let parallel_matrix = get_acres_matrix_after_iterations_with_threads(&matrix, &rule_set, num_iterations, num_threads);
+        let elapsed = start.elapsed();
+        assert!(parallel_matrix == sequential_matrix, "{} threads disagree with the sequential result", num_threads);
+        println!("{} threads: {:?}", num_threads, elapsed);
+    }
 }
\ No newline at end of file
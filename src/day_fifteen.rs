@@ -1,7 +1,7 @@
 use core::num;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 
-use crate::{day_three::Matrix, utils::read_matrix};
+use crate::{day_three::Matrix, search::{self, SearchTree, StateSpace}, utils::read_matrix};
 
 const WALL_CELL_CHAR: char = '#';
 const EMPTY_CELL_CHAR: char = '.';
@@ -116,32 +116,32 @@ fn get_attack(unit: &Unit, cave: &mut Matrix<CaveCell>) -> Option<Unit> {
     result
 }
 
-fn get_bfs_data(unit: &Unit, cave: &Matrix<CaveCell>) -> (Matrix<isize>, Matrix<(usize, usize)>) {
-    let mut visited: Matrix<bool> = Matrix::new(cave.rows, cave.cols, false);
-    let mut distances: Matrix<isize> = Matrix::new(cave.rows, cave.cols, -1);
-    let mut parents: Matrix<(usize, usize)> = Matrix::new(cave.rows, cave.cols, (0, 0));
-    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
-    visited.set(unit.row, unit.col, true);
-    distances.set(unit.row, unit.col, 0);
-    queue.push_back((unit.row, unit.col));
-    while !queue.is_empty() {
-        let (row, col) = queue.pop_front().unwrap();
+// Units move through empty cells only, and neighbours come in reading order so
+// that the first parent found is the one the rules prefer.
+struct OpenCells<'a> {
+    cave: &'a Matrix<CaveCell>
+}
+
+impl<'a> StateSpace for OpenCells<'a> {
+    type State = (usize, usize);
+
+    fn get_neighbors(&mut self, &(row, col): &(usize, usize)) -> Vec<((usize, usize), usize)> {
+        let mut neighbors: Vec<((usize, usize), usize)> = Vec::new();
         for &(row_offset, col_offset) in OFFSETS {
             let offseted_row = (row as isize) + row_offset;
             let offseted_col = (col as isize) + col_offset;
-            if offseted_row >= 0 && offseted_row < (cave.rows as isize) &&
-                offseted_col >= 0 && offseted_col < (cave.cols as isize) {
-                    let cell = cave.get(offseted_row as usize, offseted_col as usize);
-                    if cell == CaveCell::EmptyCell && !visited.get(offseted_row as usize, offseted_col as usize) {
-                        visited.set(offseted_row as usize, offseted_col as usize, true);
-                        distances.set(offseted_row as usize, offseted_col as usize, distances.get(row, col) + 1);
-                        parents.set(offseted_row as usize, offseted_col as usize, (row, col));
-                        queue.push_back((offseted_row as usize, offseted_col as usize));
-                    }
+            if offseted_row >= 0 && offseted_row < (self.cave.rows as isize) &&
+                offseted_col >= 0 && offseted_col < (self.cave.cols as isize) &&
+                self.cave.get(offseted_row as usize, offseted_col as usize) == CaveCell::EmptyCell {
+                    neighbors.push(((offseted_row as usize, offseted_col as usize), 1));
                 }
         }
+        neighbors
     }
-    (distances, parents)
+}
+
+fn get_bfs_data(unit: &Unit, cave: &Matrix<CaveCell>) -> SearchTree<(usize, usize)> {
+    search::bfs(&mut OpenCells { cave }, (unit.row, unit.col), |_| false)
 }
 
 fn is_enemy_type_adjacent(row: usize, col: usize, unit_type: &UnitType, cave: &Matrix<CaveCell>) -> bool {
@@ -161,17 +161,18 @@ fn is_enemy_type_adjacent(row: usize, col: usize, unit_type: &UnitType, cave: &M
     result
 }  
 
-fn get_destination(unit_type: &UnitType, distances: &Matrix<isize>, cave: &Matrix<CaveCell>) -> Option<(usize, usize)> {
-    let mut min_distance: isize = -1;
+fn get_destination(unit_type: &UnitType, distances: &HashMap<(usize, usize), usize>, cave: &Matrix<CaveCell>) -> Option<(usize, usize)> {
+    let mut min_distance: Option<usize> = None;
     let mut result: Option<(usize, usize)> = None;
     for row in 0..cave.rows {
         for col in 0..cave.cols {
             if cave.get(row, col) == CaveCell::EmptyCell {
                 if is_enemy_type_adjacent(row, col, unit_type, cave) {
-                    let dist = distances.get(row, col);
-                    if dist != -1 && (dist < min_distance || min_distance == -1) {
-                        min_distance = dist;
-                        result = Some((row, col));
+                    if let Some(&dist) = distances.get(&(row, col)) {
+                        if min_distance.is_none_or(|min_distance| dist < min_distance) {
+                            min_distance = Some(dist);
+                            result = Some((row, col));
+                        }
                     }
                 }
             }
@@ -180,11 +181,11 @@ fn get_destination(unit_type: &UnitType, distances: &Matrix<isize>, cave: &Matri
     result
 }
 
-fn get_first_step(dest_row: usize, dest_col: usize, src_row: usize, src_col: usize, parents: &Matrix<(usize, usize)>) -> (usize, usize) {
+fn get_first_step(dest_row: usize, dest_col: usize, src_row: usize, src_col: usize, parents: &HashMap<(usize, usize), (usize, usize)>) -> (usize, usize) {
     let mut row = dest_row;
     let mut col = dest_col;
-    while parents.get(row, col) != (src_row, src_col) {
-        let (updated_row, updated_col) = parents.get(row, col);
+    while parents[&(row, col)] != (src_row, src_col) {
+        let (updated_row, updated_col) = parents[&(row, col)];
         row = updated_row;
         col = updated_col;
     }
@@ -201,10 +202,10 @@ fn  update_for_unit(unit: &mut Unit, cave: &mut Matrix<CaveCell>) -> bool {
             cave.set(enemy_unit.row, enemy_unit.col, CaveCell::EmptyCell);
         }
     } else {
-        let (distances, parents) = get_bfs_data(unit, cave);
-        if let Some((dest_row, dest_col)) = get_destination(&unit.unit_type, &distances, cave) {
+        let tree = get_bfs_data(unit, cave);
+        if let Some((dest_row, dest_col)) = get_destination(&unit.unit_type, &tree.distances, cave) {
             found_target = true;
-            let (step_row, step_col) = get_first_step(dest_row, dest_col, unit.row, unit.col, &parents);
+            let (step_row, step_col) = get_first_step(dest_row, dest_col, unit.row, unit.col, &tree.parents);
             cave.set(unit.row, unit.col, CaveCell::EmptyCell);
             unit.row = step_row;
             unit.col = step_col;
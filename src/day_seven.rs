@@ -1,7 +1,6 @@
 use core::num;
 use std::{cmp::min, collections::{HashMap, HashSet}, usize};
-use regex::Regex;
-use crate::utils::read_lines;
+use crate::{parser::{BoxedParser, Parser, left, literal, pair, parse_all, right, satisfy}, utils::read_lines};
 struct Dependency {
     task: char,
     dependency: char
@@ -21,14 +20,22 @@ impl Task {
 
 impl Dependency {
     fn from_string(string: &str) -> Option<Dependency> {
-        let regex = Regex::new(r"Step (.) must be finished before step (.) can begin\.").ok()?;
-        let captures = regex.captures(string)?;
-        let task = captures[2].as_bytes().first().map(|byte| *byte as char)?;
-        let dependency = captures[1].as_bytes().first().map(|byte| *byte as char)?;
-        Some(Dependency { task, dependency })
+        parse_all(match_dependency(), string).ok()
     }
 }
 
+fn match_step<'a>() -> BoxedParser<'a, str, char> {
+    satisfy(|chr: char| chr.is_ascii_uppercase(), "a step")
+}
+
+// Step C must be finished before step A can begin.
+fn match_dependency<'a>() -> BoxedParser<'a, str, Dependency> {
+    pair(
+        right(literal("Step "), match_step()),
+        left(right(literal(" must be finished before step "), match_step()), literal(" can begin."))
+    ).map(|(dependency, task)| Dependency { task, dependency })
+}
+
 fn get_dependencies(strings: &Vec<String>) -> Vec<Dependency> {
     strings.iter().filter_map(|string| Dependency::from_string(string)).collect()
 }
@@ -1,9 +1,9 @@
-use std::{collections::HashMap, fs::File, hash::Hash, io::Write, ops::Range, thread::current};
+use std::{collections::{HashMap, HashSet}, fs::{self, File}, io::Write, ops::Range};
 
 use regex::Regex;
 use lazy_static::lazy_static;
 
-use crate::utils::read_lines;
+use crate::utils::{get_next_random, read_lines};
 
 const SAND_CHAR: char = '.';
 const CLAY_CHAR: char = '#';
@@ -28,10 +28,21 @@ impl Square {
     fn is_solid(&self) -> bool {
         *self == Square::Clay || *self == Square::Dry
     }
+
+    fn get_char(&self) -> char {
+        match self {
+            Square::Sand => SAND_CHAR,
+            Square::Clay => CLAY_CHAR,
+            Square::Running => RUNNING_CHAR,
+            Square::Dry => DRY_CHAR,
+        }
+    }
 }
 
 struct VerticalSlice {
     squares: HashMap<(i64, i64), Square>,
+    clay_row_min: i64,
+    clay_row_max: i64,
     row_min: i64,
     row_max: i64,
     col_min: i64,
@@ -80,6 +91,8 @@ impl VerticalSlice {
     fn new() -> VerticalSlice {
         VerticalSlice {
             squares: HashMap::new(),
+            clay_row_min: i64::MAX,
+            clay_row_max: i64::MIN,
             row_min: i64::MAX,
             row_max: i64::MIN,
             col_min: i64::MAX,
@@ -91,7 +104,7 @@ impl VerticalSlice {
         }
     }
 
-    fn from_input(clay_regions: &Vec<(Range<i64>, Range<i64>)>) -> VerticalSlice {
+    fn from_input(clay_regions: &[(Range<i64>, Range<i64>)]) -> VerticalSlice {
         let mut vertical_slice = VerticalSlice::new();
         for (row_range, col_range) in clay_regions.iter() {
             for row in row_range.clone() {
@@ -100,6 +113,8 @@ impl VerticalSlice {
                 }
             }
         }
+        vertical_slice.clay_row_min = vertical_slice.row_min;
+        vertical_slice.clay_row_max = vertical_slice.row_max;
         vertical_slice
     }
 
@@ -108,13 +123,7 @@ impl VerticalSlice {
         let mut string: String = String::new();
         for row in self.row_min..(self.row_max + 1) {
             for col in self.col_min..(self.col_max + 1) {
-                let chr = match self.get(row, col) {
-                    Square::Sand => SAND_CHAR,
-                    Square::Clay => CLAY_CHAR,
-                    Square::Running => RUNNING_CHAR,
-                    Square::Dry => DRY_CHAR,
-                };
-                string.push(chr);
+                string.push(self.get(row, col).get_char());
             }
             string.push('\n');
         }
@@ -122,127 +131,172 @@ impl VerticalSlice {
     }
 }
 
-fn get_edge(vertical_slice: &VerticalSlice, direction: WaterDirection, row: i64, col: i64) -> (i64, bool) {
-    let mut current_col = col;
-    let offset: i64 = match direction {
-        WaterDirection::Left => -1,
-        WaterDirection::Right => 1
-    };
-    while !vertical_slice.get(row, current_col + offset).is_solid() && vertical_slice.get(row + 1, current_col).is_solid() {
-        current_col += offset;
-    }
-    let hit_wall = vertical_slice.get(row, current_col + offset).is_solid();
-    (current_col, hit_wall)
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum FlowEvent {
+    Fall(i64, i64),
+    Spread(i64, i64)
 }
 
-fn get_num_water_squares(vertical_slice: &VerticalSlice) -> usize {
-    let mut res: usize = 0;
-    for row in vertical_slice.row_min..(vertical_slice.row_max + 1) {
-        for col in vertical_slice.col_min..(vertical_slice.col_max + 1) {
-            let square = vertical_slice.get(row, col);
-            if square == Square::Running || square == Square::Dry {
-                res += 1;
-            }
-        }
-    }
-    res
+#[derive(Debug, Clone)]
+struct Basin {
+    volume: usize,
+    surface_row: i64,
+    bottom_row: i64,
+    overflow_points: Vec<(i64, i64)>
 }
 
-fn get_num_still_water_squares(vertical_slice: &VerticalSlice) -> usize {
-    let mut res: usize = 0;
-    for row in vertical_slice.row_min..(vertical_slice.row_max + 1) {
-        for col in vertical_slice.col_min..(vertical_slice.col_max + 1) {
-            let square = vertical_slice.get(row, col);
-            if square == Square::Dry {
-                res += 1;
-            }
-        }
-    }
-    res
+#[derive(Debug, Clone)]
+struct FlowStatistics {
+    num_water_squares: usize,
+    num_settled_squares: usize,
+    deepest_row: i64,
+    basins: Vec<Basin>
 }
 
-fn get_hole_top(vertical_slice: &VerticalSlice, first_col: i64, second_col: i64, row: i64) -> i64 {
-    let mut current_row = row - 1;
-    let mut is_not_done = true;
-    while is_not_done {
-        if vertical_slice.get(current_row, first_col - 1).is_solid() && vertical_slice.get(current_row, second_col + 1).is_solid() {
-            for col in first_col..(second_col + 1) {
-                let square = vertical_slice.get(current_row, col);
-                if !(square == Square::Sand || square == Square::Running) {
-                    is_not_done = false;
-                }
-            }
-        } else {
-            is_not_done = false;
-        }
-        if is_not_done {
-            current_row -= 1;
+// Walks along the row until the water would fall off or hit a wall, returning the
+// last column reached and whether it is in front of a wall.
+fn get_edge(vertical_slice: &VerticalSlice, direction: WaterDirection, row: i64, col: i64) -> (i64, bool) {
+    let offset: i64 = match direction {
+        WaterDirection::Left => -1,
+        WaterDirection::Right => 1
+    };
+    let mut current_col = col;
+    while vertical_slice.get(row + 1, current_col).is_solid() {
+        if vertical_slice.get(row, current_col + offset).is_solid() {
+            return (current_col, true);
         }
+        current_col += offset;
     }
-    current_row + 1
+    (current_col, false)
 }
 
-fn fill_vertical_slice(vertical_slice: &mut VerticalSlice, spring_col: i64){
-    let spring_row = vertical_slice.row_min - 1;
-    let mut current_queue: Vec<(i64, i64)> = Vec::new();
-    vertical_slice.set(spring_row, spring_col, Square::Running);
-    current_queue.push((spring_row, spring_col));
-    let original_row_min = vertical_slice.row_min;
-    let original_row_max = vertical_slice.row_max;
-    while !current_queue.is_empty() {
-        let mut updated_queue: Vec<(i64, i64)> = Vec::new();
-        for &(row, col) in &current_queue {
-            let mut current_row = row;
-            let mut current_col = col;
-            while current_row + 1 <= vertical_slice.row_max 
-                && vertical_slice.get(current_row + 1, current_col) == Square::Sand {
+// Water falls from every spring and spreads over solid ground. A row closed by
+// walls on both sides settles, and the running water resting on it spreads again
+// one row higher, so the order in which events are handled does not matter.
+fn fill_vertical_slice(vertical_slice: &mut VerticalSlice, springs: &[(i64, i64)]) {
+    let mut pending_events: Vec<FlowEvent> = Vec::new();
+    let mut pending_events_set: HashSet<FlowEvent> = HashSet::new();
+    for &(row, col) in springs {
+        vertical_slice.set(row, col, Square::Running);
+        pending_events.push(FlowEvent::Fall(row, col));
+        pending_events_set.insert(FlowEvent::Fall(row, col));
+    }
+    let max_row = vertical_slice.clay_row_max;
+    while let Some(event) = pending_events.pop() {
+        pending_events_set.remove(&event);
+        let mut next_events: Vec<FlowEvent> = Vec::new();
+        match event {
+            FlowEvent::Fall(row, col) => {
+                let mut current_row = row;
+                while current_row < max_row && vertical_slice.get(current_row + 1, col) == Square::Sand {
                     current_row += 1;
-                    vertical_slice.set(current_row, current_col, Square::Running);
-            }
-            if current_row + 1 <= vertical_slice.row_max && vertical_slice.get(current_row + 1, current_col).is_solid() {
-                let (left_col, hit_left_wall) = get_edge(
-                    vertical_slice, 
-                    WaterDirection::Left, 
-                    current_row, 
-                    current_col
-                );
-                let (right_col, hit_right_wall) = get_edge(
-                    vertical_slice,
-                    WaterDirection::Right,
-                    current_row,
-                    current_col
-                );
+                    vertical_slice.set(current_row, col, Square::Running);
+                }
+                if current_row < max_row && vertical_slice.get(current_row + 1, col).is_solid() {
+                    next_events.push(FlowEvent::Spread(current_row, col));
+                }
+            },
+            FlowEvent::Spread(row, col) => {
+                let (left_col, hit_left_wall) = get_edge(vertical_slice, WaterDirection::Left, row, col);
+                let (right_col, hit_right_wall) = get_edge(vertical_slice, WaterDirection::Right, row, col);
                 if hit_left_wall && hit_right_wall {
-                    let top_row = get_hole_top(vertical_slice, left_col, right_col, current_row);
-                    for dry_row in top_row..(current_row + 1) {
-                        for dry_col in left_col..(right_col + 1) {
-                            vertical_slice.set(dry_row, dry_col, Square::Dry);
+                    for settled_col in left_col..=right_col {
+                        vertical_slice.set(row, settled_col, Square::Dry);
+                        if vertical_slice.get(row - 1, settled_col) == Square::Running {
+                            next_events.push(FlowEvent::Spread(row - 1, settled_col));
                         }
                     }
-                    let cell = (top_row - 1, current_col);
-                    if !updated_queue.contains(&cell) {
-                        updated_queue.push(cell);
-                    }
                 } else {
-                    for running_col in left_col..(right_col + 1) {
-                        vertical_slice.set(current_row, running_col, Square::Running);
+                    for running_col in left_col..=right_col {
+                        vertical_slice.set(row, running_col, Square::Running);
                     }
                     if !hit_left_wall {
-                        let cell = (current_row, left_col);
-                        if !updated_queue.contains(&cell) {
-                            updated_queue.push(cell);
-                        }
+                        next_events.push(FlowEvent::Fall(row, left_col));
                     }
                     if !hit_right_wall {
-                        let cell = (current_row, right_col);
-                        if !updated_queue.contains(&cell) {
-                            updated_queue.push(cell);
-                        }
+                        next_events.push(FlowEvent::Fall(row, right_col));
                     }
                 }
+            },
+        }
+        for next_event in next_events {
+            if pending_events_set.insert(next_event) {
+                pending_events.push(next_event);
+            }
+        }
+    }
+}
+
+fn get_basin(vertical_slice: &VerticalSlice, visited: &mut HashSet<(i64, i64)>, row: i64, col: i64) -> Basin {
+    let mut squares: Vec<(i64, i64)> = Vec::new();
+    let mut stack: Vec<(i64, i64)> = vec![(row, col)];
+    visited.insert((row, col));
+    while let Some((current_row, current_col)) = stack.pop() {
+        squares.push((current_row, current_col));
+        for &(next_row, next_col) in &[(current_row - 1, current_col), (current_row + 1, current_col), (current_row, current_col - 1), (current_row, current_col + 1)] {
+            if vertical_slice.get(next_row, next_col) == Square::Dry && visited.insert((next_row, next_col)) {
+                stack.push((next_row, next_col));
+            }
+        }
+    }
+    let surface_row = squares.iter().map(|&(row, _)| row).min().unwrap();
+    let bottom_row = squares.iter().map(|&(row, _)| row).max().unwrap();
+    let mut overflow_points: Vec<(i64, i64)> = squares.iter()
+        .filter(|&&(row, col)| row == surface_row && vertical_slice.get(row - 1, col) == Square::Running)
+        .flat_map(|&(row, col)| {
+            let (left_col, hit_left_wall) = get_edge(vertical_slice, WaterDirection::Left, row - 1, col);
+            let (right_col, hit_right_wall) = get_edge(vertical_slice, WaterDirection::Right, row - 1, col);
+            let mut points: Vec<(i64, i64)> = Vec::new();
+            if !hit_left_wall {
+                points.push((row - 1, left_col));
+            }
+            if !hit_right_wall {
+                points.push((row - 1, right_col));
+            }
+            points
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    overflow_points.sort_unstable();
+    Basin {
+        volume: squares.len(),
+        surface_row,
+        bottom_row,
+        overflow_points
+    }
+}
+
+// Only the squares between the highest and the lowest clay rows are counted.
+fn get_flow_statistics(vertical_slice: &VerticalSlice) -> FlowStatistics {
+    let mut num_water_squares: usize = 0;
+    let mut num_settled_squares: usize = 0;
+    let mut deepest_row = i64::MIN;
+    let mut visited: HashSet<(i64, i64)> = HashSet::new();
+    let mut basins: Vec<Basin> = Vec::new();
+    let mut water_squares: Vec<(i64, i64)> = vertical_slice.squares.iter()
+        .filter(|&(_, &square)| square == Square::Running || square == Square::Dry)
+        .map(|(&position, _)| position)
+        .collect();
+    water_squares.sort_unstable();
+    for (row, col) in water_squares {
+        deepest_row = deepest_row.max(row);
+        let square = vertical_slice.get(row, col);
+        if row >= vertical_slice.clay_row_min && row <= vertical_slice.clay_row_max {
+            num_water_squares += 1;
+            if square == Square::Dry {
+                num_settled_squares += 1;
             }
         }
-        current_queue = updated_queue;
+        if square == Square::Dry && !visited.contains(&(row, col)) {
+            basins.push(get_basin(vertical_slice, &mut visited, row, col));
+        }
+    }
+    FlowStatistics {
+        num_water_squares,
+        num_settled_squares,
+        deepest_row,
+        basins
     }
 }
 
@@ -272,24 +326,277 @@ fn get_clay_region(string: &str) -> Option<(Range<i64>, Range<i64>)> {
     }
 }
 
-pub fn solve_part_one(spring_col: i64) {
+const GENERATOR_SPRING_COL: i64 = 500;
+const GENERATOR_MAX_COL_OFFSET: i64 = 25;
+
+struct ScanGenerator {
+    state: u64,
+    current_row: i64,
+    clay_regions: Vec<(Range<i64>, Range<i64>)>
+}
+
+impl ScanGenerator {
+    fn new(seed: u64) -> ScanGenerator {
+        ScanGenerator {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+            current_row: 1,
+            clay_regions: Vec::new()
+        }
+    }
+
+    fn get_random(&mut self, range: Range<i64>) -> i64 {
+        range.start + (get_next_random(&mut self.state) % (range.end - range.start) as u64) as i64
+    }
+
+    fn add_vertical_vein(&mut self, col: i64, top_row: i64, bottom_row: i64) {
+        self.clay_regions.push((top_row..(bottom_row + 1), col..(col + 1)));
+    }
+
+    fn add_horizontal_vein(&mut self, row: i64, left_col: i64, right_col: i64) {
+        self.clay_regions.push((row..(row + 1), left_col..(right_col + 1)));
+    }
+
+    // The walls may have different heights, so the water spills over the lower one.
+    fn add_bucket(&mut self, left_col: i64, right_col: i64, top_row: i64, bottom_row: i64) {
+        let left_top_row = top_row + self.get_random(0..3);
+        let right_top_row = top_row + self.get_random(0..3);
+        self.add_vertical_vein(left_col, left_top_row, bottom_row);
+        self.add_vertical_vein(right_col, right_top_row, bottom_row);
+        self.add_horizontal_vein(bottom_row, left_col, right_col);
+    }
+
+    fn add_feature(&mut self) {
+        let center_col = GENERATOR_SPRING_COL + self.get_random(-GENERATOR_MAX_COL_OFFSET..(GENERATOR_MAX_COL_OFFSET + 1));
+        let half_width = self.get_random(2..12);
+        let left_col = center_col - half_width;
+        let right_col = center_col + half_width;
+        let top_row = self.current_row;
+        let height = match self.get_random(0..3) {
+            0 => {
+                self.add_horizontal_vein(top_row, left_col, right_col);
+                1
+            },
+            1 => {
+                let height = self.get_random(3..10);
+                self.add_bucket(left_col, right_col, top_row, top_row + height);
+                height
+            },
+            _ => {
+                let height = self.get_random(8..16);
+                self.add_bucket(left_col - 4, right_col + 4, top_row, top_row + height);
+                let inner_top_row = top_row + self.get_random(3..5);
+                let inner_bottom_row = top_row + height - self.get_random(2..4);
+                self.add_bucket(left_col, right_col, inner_top_row, inner_bottom_row);
+                height
+            },
+        };
+        self.current_row = top_row + height + self.get_random(2..8);
+    }
+}
+
+// Generates a scan of buckets, buckets nested inside bigger ones and flat shelves,
+// stacked from top to bottom around the column of the default spring.
+fn generate_clay_regions(seed: u64, num_features: usize) -> Vec<(Range<i64>, Range<i64>)> {
+    let mut generator = ScanGenerator::new(seed);
+    for _ in 0..num_features {
+        generator.add_feature();
+    }
+    generator.clay_regions
+}
+
+fn get_clay_region_string(row_range: &Range<i64>, col_range: &Range<i64>) -> String {
+    if col_range.end - col_range.start == 1 {
+        format!("x={}, y={}..{}", col_range.start, row_range.start, row_range.end - 1)
+    } else {
+        format!("y={}, x={}..{}", row_range.start, col_range.start, col_range.end - 1)
+    }
+}
+
+fn get_scan_string(clay_regions: &[(Range<i64>, Range<i64>)]) -> String {
+    clay_regions.iter()
+        .map(|(row_range, col_range)| get_clay_region_string(row_range, col_range))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// A deliberately naive model of the fill, independent of the event-driven one: local
+// rules applied over a dense grid until nothing changes. Sand under running water
+// runs, sand next to running water that rests on solid ground runs, and a stretch
+// of running water on solid ground closed at both ends settles. The grid
+// spans the rows from the springs down to the lowest clay and one column past the
+// clay on each side, which is as far as water can spread.
+struct RelaxedSlice {
+    row_min: i64,
+    col_min: i64,
+    squares: Vec<Vec<Square>>
+}
+
+impl RelaxedSlice {
+    fn get(&self, row: i64, col: i64) -> Square {
+        if row < self.row_min || col < self.col_min {
+            return Square::Sand;
+        }
+        self.squares.get((row - self.row_min) as usize)
+            .and_then(|squares| squares.get((col - self.col_min) as usize))
+            .copied()
+            .unwrap_or(Square::Sand)
+    }
+
+    fn set(&mut self, row: i64, col: i64, square: Square) {
+        self.squares[(row - self.row_min) as usize][(col - self.col_min) as usize] = square;
+    }
+
+    fn rows(&self) -> Range<i64> {
+        self.row_min..(self.row_min + self.squares.len() as i64)
+    }
+
+    fn cols(&self) -> Range<i64> {
+        self.col_min..(self.col_min + self.squares[0].len() as i64)
+    }
+
+    // The running stretch through (row, col) resting on solid ground, if it is
+    // closed on both ends. Squares settle one at a time, so the stretch can end in
+    // the part of it that already settled.
+    fn get_closed_stretch(&self, row: i64, col: i64) -> Option<Range<i64>> {
+        let is_resting = |current_col: i64| self.get(row, current_col) == Square::Running &&
+            self.get(row + 1, current_col).is_solid();
+        let mut left_col = col;
+        while is_resting(left_col - 1) {
+            left_col -= 1;
+        }
+        let mut right_col = col;
+        while is_resting(right_col + 1) {
+            right_col += 1;
+        }
+        let is_closed = self.get(row, left_col - 1).is_solid() && self.get(row, right_col + 1).is_solid();
+        if is_resting(col) && is_closed { Some(left_col..(right_col + 1)) } else { None }
+    }
+
+    fn get_next_square(&self, row: i64, col: i64) -> Square {
+        let square = self.get(row, col);
+        let is_resting_running = |neighbor_col: i64| self.get(row, neighbor_col) == Square::Running &&
+            self.get(row + 1, neighbor_col).is_solid();
+        match square {
+            Square::Sand if self.get(row - 1, col) == Square::Running ||
+                is_resting_running(col - 1) || is_resting_running(col + 1) => Square::Running,
+            Square::Running if self.get_closed_stretch(row, col).is_some() => Square::Dry,
+            _ => square,
+        }
+    }
+
+    fn new(clay_regions: &[(Range<i64>, Range<i64>)], springs: &[(i64, i64)]) -> RelaxedSlice {
+        let clay_squares = || clay_regions.iter()
+            .flat_map(|(row_range, col_range)| row_range.clone().flat_map(move |row| col_range.clone().map(move |col| (row, col))));
+        let row_min = springs.iter().map(|&(row, _)| row).min().unwrap_or(0);
+        let row_max = clay_squares().map(|(row, _)| row).max().unwrap_or(row_min);
+        let col_min = clay_squares().map(|(_, col)| col).chain(springs.iter().map(|&(_, col)| col)).min().unwrap_or(0) - 1;
+        let col_max = clay_squares().map(|(_, col)| col).chain(springs.iter().map(|&(_, col)| col)).max().unwrap_or(0) + 1;
+        let mut relaxed_slice = RelaxedSlice {
+            row_min,
+            col_min,
+            squares: vec![vec![Square::Sand; (col_max - col_min + 1) as usize]; (row_max - row_min + 1) as usize]
+        };
+        for (row, col) in clay_squares() {
+            relaxed_slice.set(row, col, Square::Clay);
+        }
+        for &(row, col) in springs {
+            relaxed_slice.set(row, col, Square::Running);
+        }
+        relaxed_slice
+    }
+
+    fn fill(&mut self) {
+        let mut is_changed = true;
+        while is_changed {
+            is_changed = false;
+            for row in self.rows() {
+                for col in self.cols().chain(self.cols().rev()) {
+                    let next_square = self.get_next_square(row, col);
+                    if next_square != self.get(row, col) {
+                        self.set(row, col, next_square);
+                        is_changed = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Compares the fill square by square with the naive model, so a wrong count can not
+// hide behind squares that each look plausible on their own.
+fn check_filled_vertical_slice(vertical_slice: &VerticalSlice, clay_regions: &[(Range<i64>, Range<i64>)], springs: &[(i64, i64)]) -> Result<(), String> {
+    let mut relaxed_slice = RelaxedSlice::new(clay_regions, springs);
+    relaxed_slice.fill();
+    let (rows, cols) = (relaxed_slice.rows(), relaxed_slice.cols());
+    if let Some(&(row, col)) = vertical_slice.squares.keys().find(|&&(row, col)| !rows.contains(&row) || !cols.contains(&col)) {
+        return Err(format!("x={}, y={} is outside the area water can reach", col, row));
+    }
+    for row in rows {
+        for col in cols.clone() {
+            let (square, expected_square) = (vertical_slice.get(row, col), relaxed_slice.get(row, col));
+            if square != expected_square {
+                return Err(format!("x={}, y={} is {} but should be {}", col, row, square.get_char(), expected_square.get_char()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_flow_statistics_for_regions(clay_regions: &[(Range<i64>, Range<i64>)], springs: &[(i64, i64)]) -> FlowStatistics {
+    let mut vertical_slice = VerticalSlice::from_input(clay_regions);
+    fill_vertical_slice(&mut vertical_slice, springs);
+    get_flow_statistics(&vertical_slice)
+}
+
+fn get_flow_statistics_for_springs(springs: &[(i64, i64)]) -> FlowStatistics {
     let lines = read_lines("day_seventeen.txt");
     let clay_regions: Vec<_> = lines.iter()
         .filter_map(|string| get_clay_region(string))
         .collect();
-    let mut vertical_slice = VerticalSlice::from_input(&clay_regions);
-    fill_vertical_slice(&mut vertical_slice, spring_col);
-    let answer = get_num_water_squares(&vertical_slice) - 1;
+    get_flow_statistics_for_regions(&clay_regions, springs)
+}
+
+pub fn solve_part_one(spring_col: i64) {
+    let statistics = get_flow_statistics_for_springs(&[(0, spring_col)]);
+    let answer = statistics.num_water_squares;
     println!("{}", answer);
 }
 
 pub fn solve_part_two(spring_col: i64) {
-    let lines = read_lines("day_seventeen.txt");
-    let clay_regions: Vec<_> = lines.iter()
-        .filter_map(|string| get_clay_region(string))
-        .collect();
-    let mut vertical_slice = VerticalSlice::from_input(&clay_regions);
-    fill_vertical_slice(&mut vertical_slice, spring_col);
-    let answer = get_num_still_water_squares(&vertical_slice);
+    let statistics = get_flow_statistics_for_springs(&[(0, spring_col)]);
+    let answer = statistics.num_settled_squares;
     println!("{}", answer);
+}
+
+pub fn print_basins(springs: &[(i64, i64)]) {
+    let statistics = get_flow_statistics_for_springs(springs);
+    for basin in &statistics.basins {
+        println!("rows {}..={}: {} settled, overflows at {:?}", basin.surface_row, basin.bottom_row, basin.volume, basin.overflow_points);
+    }
+    println!("{} water squares, {} settled, deepest row {}", statistics.num_water_squares, statistics.num_settled_squares, statistics.deepest_row);
+}
+
+pub fn generate_scan(seed: u64, num_features: usize, path: &str) {
+    let clay_regions = generate_clay_regions(seed, num_features);
+    fs::write(path, get_scan_string(&clay_regions)).expect("Error writing file");
+    let statistics = get_flow_statistics_for_regions(&clay_regions, &[(0, GENERATOR_SPRING_COL)]);
+    println!("{}", statistics.num_water_squares);
+    println!("{}", statistics.num_settled_squares);
+}
+
+pub fn fuzz_fill(num_seeds: u64, num_features: usize) {
+    for seed in 0..num_seeds {
+        let clay_regions = generate_clay_regions(seed, num_features);
+        let parsed_clay_regions: Vec<_> = get_scan_string(&clay_regions)
+            .split('\n')
+            .filter_map(get_clay_region)
+            .collect();
+        assert!(parsed_clay_regions == clay_regions, "seed {}: the scan does not parse back", seed);
+        let mut vertical_slice = VerticalSlice::from_input(&clay_regions);
+        fill_vertical_slice(&mut vertical_slice, &[(0, GENERATOR_SPRING_COL)]);
+        if let Err(message) = check_filled_vertical_slice(&vertical_slice, &clay_regions, &[(0, GENERATOR_SPRING_COL)]) {
+            panic!("seed {}: {}", seed, message);
+        }
+    }
+    println!("{} scans passed", num_seeds);
 }
\ No newline at end of file
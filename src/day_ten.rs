@@ -1,22 +1,28 @@
-use std::{ops::{Add, AddAssign, SubAssign}, usize};
+use std::{fs, ops::{Add, AddAssign, SubAssign}, usize};
 
-use regex::Regex;
+use crate::{
+    day_three::Matrix,
+    ocr::Ocr,
+    parser::{BoxedParser, ParseError, Parser, integer, left, literal, many, match_item, pair, parse_all, recover, right, whitespace}
+};
+
+const LIT_CHAR: char = '#';
 
-use crate::{day_three::Matrix, utils::read_lines};
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Vector {
     x: i64,
     y: i64
 }
 
-impl Vector {
-    fn from_string(string: &str) -> Option<Vector> {
-        let regex = Regex::new(r"<([ \-]?\d+), ([ \-]?\d+)>").ok()?;
-        let captures = regex.captures(string)?;
-        let x = captures[1].trim_start().parse::<i64>().ok()?;
-        let y = captures[2].trim_start().parse::<i64>().ok()?;
-        Some(Vector { x, y })
-    }
+// <x, y>, with the numbers padded by spaces
+fn match_vector<'a>() -> BoxedParser<'a, str, Vector> {
+    right(
+        match_item('<'),
+        pair(
+            left(right(whitespace(), integer()), match_item(',')),
+            left(right(whitespace(), integer()), match_item('>'))
+        )
+    ).map(|(x, y)| Vector { x, y })
 }
 
 impl Add for Vector {
@@ -49,74 +55,93 @@ struct Star {
     velocity: Vector
 }
 
-impl Star {
-    fn from_string(string: &str) -> Option<Star> {
-        let regex = Regex::new(r"position=(.*) velocity=(.*)").ok()?;
-        let captures = regex.captures(string)?;
-        let position = Vector::from_string(&captures[1])?;
-        let velocity = Vector::from_string(&captures[2])?;
-        Some(Star{ position, velocity })
-    }
+// position=<x, y> velocity=<x, y>
+fn match_star<'a>() -> BoxedParser<'a, str, Star> {
+    pair(
+        right(literal("position="), match_vector()),
+        right(literal(" velocity="), match_vector())
+    ).map(|(position, velocity)| Star { position, velocity })
+}
 
-    fn update(&mut self) {
-        self.position += self.velocity;
-    }
+// One star per line. A line that does not parse is reported and skipped, so the
+// other stars are still read.
+fn match_stars<'a>() -> BoxedParser<'a, str, Vec<Result<Star, ParseError>>> {
+    many(recover(left(match_star(), match_item('\n')), match_item('\n')))
+}
 
-    fn revert(&mut self) {
-        self.position -= self.velocity;
+impl Star {
+    fn get_position_at(&self, second: i64) -> Vector {
+        Vector {
+            x: self.position.x + self.velocity.x * second,
+            y: self.position.y + self.velocity.y * second
+        }
     }
 }
 
-fn get_stars_area(stars: &Vec<Star>) -> i64 {
-    let x_min = stars.iter().map(|star| star.position.x).min().unwrap();
-    let x_max = stars.iter().map(|star| star.position.x).max().unwrap();
-    let y_min = stars.iter().map(|star| star.position.y).min().unwrap();
-    let y_max = stars.iter().map(|star| star.position.y).max().unwrap();
+fn get_stars_area_at(stars: &[Star], second: i64) -> i64 {
+    let positions: Vec<Vector> = stars.iter()
+        .map(|star| star.get_position_at(second))
+        .collect();
+    let x_min = positions.iter().map(|position| position.x).min().unwrap();
+    let x_max = positions.iter().map(|position| position.x).max().unwrap();
+    let y_min = positions.iter().map(|position| position.y).min().unwrap();
+    let y_max = positions.iter().map(|position| position.y).max().unwrap();
     (x_max - x_min + 1) * (y_max - y_min + 1)
 }
 
-fn update_stars_to_message(stars: &mut Vec<Star>) {
-    let mut current_area = get_stars_area(stars);
-    let mut previous_area = current_area + 1;
-    while current_area < previous_area {
-        for star in stars.iter_mut() {
-            star.update();
-        }
-        previous_area = current_area;
-        current_area = get_stars_area(stars);
+// The second at which the stars are the closest to their centroid, in the least
+// squares sense: the spread around the centroid is a quadratic in time whose
+// minimum is -cov(position, velocity) / var(velocity), summed over both axes.
+fn get_estimated_message_seconds(stars: &[Star]) -> i64 {
+    let num_stars = stars.len() as f64;
+    let mean = |values: &dyn Fn(&Star) -> i64| stars.iter().map(|star| values(star) as f64).sum::<f64>() / num_stars;
+    let mean_x = mean(&|star| star.position.x);
+    let mean_y = mean(&|star| star.position.y);
+    let mean_velocity_x = mean(&|star| star.velocity.x);
+    let mean_velocity_y = mean(&|star| star.velocity.y);
+    let mut covariance: f64 = 0.0;
+    let mut variance: f64 = 0.0;
+    for star in stars {
+        let velocity_x = star.velocity.x as f64 - mean_velocity_x;
+        let velocity_y = star.velocity.y as f64 - mean_velocity_y;
+        covariance += (star.position.x as f64 - mean_x) * velocity_x + (star.position.y as f64 - mean_y) * velocity_y;
+        variance += velocity_x * velocity_x + velocity_y * velocity_y;
     }
-    for star in stars.iter_mut() {
-        star.revert();
+    if variance == 0.0 {
+        0
+    } else {
+        (-covariance / variance).round().max(0.0) as i64
     }
 }
 
-fn get_message_seconds(stars: &mut Vec<Star>) -> u64 {
-    let mut second: u64 = 0;
-    let mut current_area = get_stars_area(stars);
-    let mut previous_area = current_area + 1;
-    while current_area < previous_area {
+// Starts from the estimate and walks to the nearby second with the smallest
+// bounding box, which is where the message is readable.
+fn get_message_seconds(stars: &[Star]) -> i64 {
+    let mut second = get_estimated_message_seconds(stars);
+    while get_stars_area_at(stars, second + 1) < get_stars_area_at(stars, second) {
         second += 1;
-        for star in stars.iter_mut() {
-            star.update();
-        }
-        previous_area = current_area;
-        current_area = get_stars_area(stars);
     }
-    second - 1
+    while second > 0 && get_stars_area_at(stars, second - 1) < get_stars_area_at(stars, second) {
+        second -= 1;
+    }
+    second
 }
 
-fn get_display_matrix(stars: &Vec<Star>) -> Matrix<char> {
-    let x_min = stars.iter().map(|star| star.position.x).min().unwrap();
-    let x_max = stars.iter().map(|star| star.position.x).max().unwrap();
-    let y_min = stars.iter().map(|star| star.position.y).min().unwrap();
-    let y_max = stars.iter().map(|star| star.position.y).max().unwrap();
+fn get_display_matrix(stars: &[Star], second: i64) -> Matrix<char> {
+    let positions: Vec<Vector> = stars.iter()
+        .map(|star| star.get_position_at(second))
+        .collect();
+    let x_min = positions.iter().map(|position| position.x).min().unwrap();
+    let x_max = positions.iter().map(|position| position.x).max().unwrap();
+    let y_min = positions.iter().map(|position| position.y).min().unwrap();
+    let y_max = positions.iter().map(|position| position.y).max().unwrap();
     let rows = (y_max - y_min + 1) as usize;
     let cols = (x_max - x_min + 1) as usize;
     let mut matrix: Matrix<char> = Matrix::new(rows, cols, ' ');
-    for star in stars {
-        let row = (star.position.y - y_min) as usize;
-        let col = (star.position.x - x_min) as usize;
-        matrix.set(row, col, '#');
+    for position in positions {
+        let row = (position.y - y_min) as usize;
+        let col = (position.x - x_min) as usize;
+        matrix.set(row, col, LIT_CHAR);
     }
     matrix
 }
@@ -130,19 +155,51 @@ fn display_matrix(matrix: &Matrix<char>) {
     }
 }
 
-fn get_stars(path: &str) -> Vec<Star> {
-    read_lines(path).iter().filter_map(|string| Star::from_string(string)).collect()
+// Invalid lines are reported and skipped; there has to be at least one star left.
+fn get_stars(path: &str) -> Result<Vec<Star>, String> {
+    let mut input = fs::read_to_string(path).expect("Error reading file");
+    if !input.ends_with('\n') {
+        input.push('\n');
+    }
+    let stars: Vec<Star> = parse_all(match_stars(), input.as_str())
+        .map_err(|error| format!("Invalid stars at {}", error))?
+        .into_iter()
+        .filter_map(|star| match star {
+            Ok(star) => Some(star),
+            Err(error) => {
+                println!("Skipped invalid star at {}", error);
+                None
+            },
+        })
+        .collect();
+    if stars.is_empty() {
+        return Err(String::from("No stars"));
+    }
+    Ok(stars)
 }
 
 pub fn solve_part_one() {
-    let mut stars = get_stars("day_ten.txt");
-    update_stars_to_message(&mut stars);
-    let display = get_display_matrix(&stars);
-    display_matrix(&display);
+    let stars = match get_stars("day_ten.txt") {
+        Ok(stars) => stars,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        },
+    };
+    let second = get_message_seconds(&stars);
+    let display = get_display_matrix(&stars, second);
+    match Ocr::new().recognize(&display) {
+        Ok(message) => println!("{}", message),
+        Err(error) => {
+            println!("{}", error);
+            display_matrix(&display);
+        },
+    }
 }
 
 pub fn solve_part_two() {
-    let mut stars = get_stars("day_ten.txt");
-    let answer = get_message_seconds(&mut stars);
-    println!("{}", answer);
+    match get_stars("day_ten.txt") {
+        Ok(stars) => println!("{}", get_message_seconds(&stars)),
+        Err(message) => println!("{}", message),
+    }
 }
\ No newline at end of file
@@ -1,8 +1,11 @@
-use std::{collections::{HashMap, HashSet}, iter::FromIterator};
+use std::{cell::Cell, collections::{HashMap, HashSet}, fmt, iter::FromIterator, rc::Rc};
 
-use crate::{day_three::Matrix, utils::read_matrix};
+use lazy_static::lazy_static;
+use regex::Regex;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+use crate::{day_three::Matrix, utils::{get_next_random, read_lines, read_matrix}};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
 struct Position {
     row: usize,
     col: usize
@@ -61,6 +64,32 @@ impl Direction {
             TurnDirection::Right => self.get_right(),
         }
     }
+
+    fn get_opposite(&self) -> Direction {
+        self.get_left().get_left()
+    }
+
+    fn get_cart_char(&self) -> char {
+        match self {
+            Direction::North => CART_NORTH_CHAR,
+            Direction::South => CART_SOUTH_CHAR,
+            Direction::East => CART_EAST_CHAR,
+            Direction::West => CART_WEST_CHAR,
+        }
+    }
+
+    fn get_offset(&self) -> (i64, i64) {
+        match self {
+            Direction::North => (-1, 0),
+            Direction::South => (1, 0),
+            Direction::East => (0, 1),
+            Direction::West => (0, -1),
+        }
+    }
+
+    fn is_horizontal(&self) -> bool {
+        *self == Direction::East || *self == Direction::West
+    }
 }
 
 trait Turn {
@@ -131,28 +160,132 @@ enum TurnDirection {
 }
 
 impl TurnDirection {
-    fn get_next_turn_direction(&self) -> TurnDirection {
-        match *self {
-            TurnDirection::Left => TurnDirection::Straight,
-            TurnDirection::Straight => TurnDirection::Right,
-            TurnDirection::Right => TurnDirection::Left,
+    fn from_string(string: &str) -> Option<TurnDirection> {
+        match string {
+            "left" => Some(TurnDirection::Left),
+            "straight" => Some(TurnDirection::Straight),
+            "right" => Some(TurnDirection::Right),
+            _ => None,
         }
     }
 }
 
+const TURN_DIRECTIONS: &[TurnDirection; 3] = &[
+    TurnDirection::Left,
+    TurnDirection::Straight,
+    TurnDirection::Right
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum IntersectionPolicy {
+    Sequence(Vec<TurnDirection>),
+    Random(u64),
+    AlwaysStraight,
+}
+
+impl IntersectionPolicy {
+    // Either "always-straight", "random <seed>" or a list of "left", "straight"
+    // and "right" that the cart cycles through.
+    fn from_string(string: &str) -> Result<IntersectionPolicy, String> {
+        let words: Vec<&str> = string.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Err(String::from("missing intersection policy")),
+            ["always-straight"] => Ok(IntersectionPolicy::AlwaysStraight),
+            ["random", seed] => seed.parse::<u64>()
+                .map(IntersectionPolicy::Random)
+                .map_err(|_| format!("invalid random seed \"{}\"", seed)),
+            _ => words.iter()
+                .map(|word| TurnDirection::from_string(word).ok_or(format!("unknown turn direction \"{}\"", word)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(IntersectionPolicy::Sequence),
+        }
+    }
+
+    fn get_initial_state(&self, cart_id: usize) -> u64 {
+        match self {
+            IntersectionPolicy::Random(seed) => (seed ^ (cart_id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1,
+            _ => 0,
+        }
+    }
+
+    fn get_turn_direction(&self, state: &mut u64) -> TurnDirection {
+        match self {
+            IntersectionPolicy::Sequence(turn_directions) => {
+                let turn_direction = turn_directions[(*state as usize) % turn_directions.len()].clone();
+                *state += 1;
+                turn_direction
+            },
+            IntersectionPolicy::Random(_) => {
+                TURN_DIRECTIONS[(get_next_random(state) % 3) as usize].clone()
+            },
+            IntersectionPolicy::AlwaysStraight => TurnDirection::Straight,
+        }
+    }
+}
+
+impl Default for IntersectionPolicy {
+    fn default() -> IntersectionPolicy {
+        IntersectionPolicy::Sequence(TURN_DIRECTIONS.to_vec())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct IntersectionConfig {
+    default_policy: IntersectionPolicy,
+    cart_policies: HashMap<usize, IntersectionPolicy>
+}
+
+impl IntersectionConfig {
+    // One "default: <policy>" or "cart <id>: <policy>" entry per line, carts being
+    // numbered in reading order. Blank lines and lines starting with '#' are ignored.
+    fn from_lines(lines: &[String]) -> Result<IntersectionConfig, String> {
+        lazy_static! {
+            static ref REGEX: Regex = Regex::new(r"^(?:default|cart (\d+)):(.*)$").unwrap();
+        }
+        let mut config = IntersectionConfig::default();
+        for (index, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let captures = REGEX.captures(line)
+                .ok_or(format!("line {}: expected \"default:\" or \"cart <id>:\"", index + 1))?;
+            let policy = IntersectionPolicy::from_string(&captures[2])
+                .map_err(|message| format!("line {}: {}", index + 1, message))?;
+            match captures.get(1) {
+                Some(cart_id) => {
+                    let cart_id = cart_id.as_str().parse::<usize>()
+                        .map_err(|_| format!("line {}: invalid cart id", index + 1))?;
+                    config.cart_policies.insert(cart_id, policy);
+                },
+                None => config.default_policy = policy,
+            }
+        }
+        Ok(config)
+    }
+
+    fn get_policy(&self, cart_id: usize) -> IntersectionPolicy {
+        self.cart_policies.get(&cart_id)
+            .unwrap_or(&self.default_policy)
+            .clone()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Cart {
     row: usize,
     col: usize,
     direction: Direction,
-    turn_direction: TurnDirection,
+    intersection_policy: IntersectionPolicy,
+    intersection_state: u64,
     id: usize
 }
 
 impl Cart {
-    fn new(row: usize, col: usize, direction: &Direction, id: usize) -> Cart {
+    fn new(row: usize, col: usize, direction: &Direction, id: usize, intersection_policy: IntersectionPolicy) -> Cart {
+        let intersection_state = intersection_policy.get_initial_state(id);
         Cart {
-            row, col, direction: direction.clone(), turn_direction: TurnDirection::Left, id
+            row, col, direction: direction.clone(), intersection_policy, intersection_state, id
         }
     }
 
@@ -193,8 +326,8 @@ impl Cart {
     }
 
     fn update_on_intersection(&mut self) {
-        self.direction = self.direction.get_turn_direction(&self.turn_direction);
-        self.turn_direction = self.turn_direction.get_next_turn_direction();
+        let turn_direction = self.intersection_policy.get_turn_direction(&mut self.intersection_state);
+        self.direction = self.direction.get_turn_direction(&turn_direction);
     }
 
     fn get_position(&self) -> Position {
@@ -214,13 +347,25 @@ enum TrackElement {
 }
 
 struct Tracks {
+    chars: Matrix<char>,
     matrix: Matrix<TrackElement>,
     turns: HashMap<(usize, usize), Vec<Box<dyn Turn>>>
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum CartEvent {
+    Moved { id: usize, from: Position, to: Position },
+    Turned { id: usize, position: Position, from: Direction, to: Direction },
+    Crashed { position: Position, moving_id: usize, hit_id: usize },
+}
+
+type CartEventListener = Box<dyn FnMut(&CartEvent)>;
+
 struct State {
     tracks: Tracks,
-    carts: Vec<Cart>
+    carts: Vec<Cart>,
+    crash_sites: Vec<Position>,
+    listeners: Vec<CartEventListener>
 }
 
 const FIRST_STRAIGHT_CHAR: char = '-';
@@ -233,6 +378,138 @@ const CART_NORTH_CHAR: char = '^';
 const CART_SOUTH_CHAR: char = 'v';
 const CART_EAST_CHAR: char = '>';
 const CART_WEST_CHAR: char = '<';
+const CRASH_CHAR: char = 'X';
+// With some intersection policies carts can run forever without crashing, so runs
+// give up after this many ticks, far more than the puzzle's carts need.
+const MAX_TICKS: usize = 1_000_000;
+const DIRECTIONS: &[Direction; 4] = &[
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TrackErrorKind {
+    UnknownCharacter(char),
+    DanglingSegment(Direction),
+    DisconnectedCurve,
+    CartOffTrack(Direction),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TrackError {
+    position: Position,
+    kind: TrackErrorKind
+}
+
+impl fmt::Display for TrackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}: ", self.position.col, self.position.row)?;
+        match &self.kind {
+            TrackErrorKind::UnknownCharacter(chr) => write!(f, "unknown character '{}'", chr),
+            TrackErrorKind::DanglingSegment(direction) => write!(f, "track leads {:?} to nothing", direction),
+            TrackErrorKind::DisconnectedCurve => write!(f, "curve does not connect two tracks"),
+            TrackErrorKind::CartOffTrack(direction) => write!(f, "cart has no track to its {:?}", direction),
+        }
+    }
+}
+
+fn get_neighbor_char(char_matrix: &Matrix<char>, row: usize, col: usize, direction: &Direction) -> char {
+    let (offset_row, offset_col) = direction.get_offset();
+    let neighbor_row = row as i64 + offset_row;
+    let neighbor_col = col as i64 + offset_col;
+    if neighbor_row < 0 || neighbor_col < 0 || neighbor_row as usize >= char_matrix.rows || neighbor_col as usize >= char_matrix.cols {
+        NOTHING_CHAR
+    } else {
+        char_matrix.get(neighbor_row as usize, neighbor_col as usize)
+    }
+}
+
+fn can_connect_towards(chr: char, direction: &Direction) -> bool {
+    match chr {
+        INTERSECTION_CHAR | FIRST_TURN_CHAR | SECOND_TURN_CHAR => true,
+        FIRST_STRAIGHT_CHAR | CART_EAST_CHAR | CART_WEST_CHAR => direction.is_horizontal(),
+        SECOND_STRAIGHT_CHAR | CART_NORTH_CHAR | CART_SOUTH_CHAR => !direction.is_horizontal(),
+        _ => false,
+    }
+}
+
+// A curve is one of two corners; keep every corner whose both neighbors can lead
+// into it. Two corners touching back to back keep all four connections.
+fn get_curve_connections(char_matrix: &Matrix<char>, row: usize, col: usize, chr: char) -> Vec<Direction> {
+    let corners = if chr == SECOND_TURN_CHAR {
+        [[Direction::South, Direction::East], [Direction::North, Direction::West]]
+    } else {
+        [[Direction::South, Direction::West], [Direction::North, Direction::East]]
+    };
+    corners.iter()
+        .filter(|corner| {
+            corner.iter().all(|direction| {
+                let neighbor_char = get_neighbor_char(char_matrix, row, col, direction);
+                can_connect_towards(neighbor_char, &direction.get_opposite())
+            })
+        })
+        .flat_map(|corner| corner.iter().cloned())
+        .collect()
+}
+
+fn get_connections(char_matrix: &Matrix<char>, row: usize, col: usize) -> Result<Vec<Direction>, TrackErrorKind> {
+    let chr = char_matrix.get(row, col);
+    match chr {
+        NOTHING_CHAR => Ok(vec![]),
+        FIRST_STRAIGHT_CHAR | CART_EAST_CHAR | CART_WEST_CHAR => Ok(vec![Direction::East, Direction::West]),
+        SECOND_STRAIGHT_CHAR | CART_NORTH_CHAR | CART_SOUTH_CHAR => Ok(vec![Direction::North, Direction::South]),
+        INTERSECTION_CHAR => Ok(DIRECTIONS.to_vec()),
+        FIRST_TURN_CHAR | SECOND_TURN_CHAR => {
+            let connections = get_curve_connections(char_matrix, row, col, chr);
+            if connections.is_empty() {
+                Err(TrackErrorKind::DisconnectedCurve)
+            } else {
+                Ok(connections)
+            }
+        },
+        _ => Err(TrackErrorKind::UnknownCharacter(chr)),
+    }
+}
+
+fn validate_tracks(char_matrix: &Matrix<char>) -> Result<(), Vec<TrackError>> {
+    let mut errors: Vec<TrackError> = Vec::new();
+    let mut connections: Matrix<Vec<Direction>> = Matrix::new(char_matrix.rows, char_matrix.cols, vec![]);
+    for row in 0..char_matrix.rows {
+        for col in 0..char_matrix.cols {
+            match get_connections(char_matrix, row, col) {
+                Ok(cell_connections) => connections.set(row, col, cell_connections),
+                Err(kind) => errors.push(TrackError { position: Position { row, col }, kind }),
+            }
+        }
+    }
+    for row in 0..char_matrix.rows {
+        for col in 0..char_matrix.cols {
+            for direction in connections.get_ref(row, col) {
+                let (offset_row, offset_col) = direction.get_offset();
+                let neighbor_row = row as i64 + offset_row;
+                let neighbor_col = col as i64 + offset_col;
+                let is_connected = neighbor_row >= 0 && neighbor_col >= 0 &&
+                    (neighbor_row as usize) < char_matrix.rows && (neighbor_col as usize) < char_matrix.cols &&
+                    connections.get_ref(neighbor_row as usize, neighbor_col as usize).contains(&direction.get_opposite());
+                if !is_connected {
+                    let kind = if Direction::from_char(char_matrix.get(row, col)).is_some() {
+                        TrackErrorKind::CartOffTrack(direction.clone())
+                    } else {
+                        TrackErrorKind::DanglingSegment(direction.clone())
+                    };
+                    errors.push(TrackError { position: Position { row, col }, kind });
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
 
 impl Tracks {
     fn new(char_matrix: &Matrix<char>) -> Tracks {
@@ -274,148 +551,210 @@ impl Tracks {
                 }
             }
         }
+        let chars = char_matrix.map(NOTHING_CHAR, |&chr| {
+            match chr {
+                CART_EAST_CHAR | CART_WEST_CHAR => FIRST_STRAIGHT_CHAR,
+                CART_NORTH_CHAR | CART_SOUTH_CHAR => SECOND_STRAIGHT_CHAR,
+                _ => chr,
+            }
+        });
         Tracks {
-            matrix, turns
+            chars, matrix, turns
         }
     }
 }
 
 impl State {
-    fn new(char_matrix: &Matrix<char>) -> State {
+    fn with_intersection_config(char_matrix: &Matrix<char>, config: &IntersectionConfig) -> Result<State, Vec<TrackError>> {
+        validate_tracks(char_matrix)?;
         let tracks = Tracks::new(char_matrix);
         let mut carts: Vec<Cart> = Vec::new();
         for row in 0..char_matrix.rows {
             for col in 0..char_matrix.cols {
                 let chr = char_matrix.get(row, col);
                 if let Some(direction) = Direction::from_char(chr) {
-                    let cart = Cart::new(row, col, &direction, carts.len());
+                    let id = carts.len();
+                    let cart = Cart::new(row, col, &direction, id, config.get_policy(id));
                     carts.push(cart);
                 }
             }
         }
-        State {
-            tracks, 
-            carts
-        }
+        Ok(
+            State {
+                tracks, 
+                carts,
+                crash_sites: Vec::new(),
+                listeners: Vec::new()
+            }
+        )
     }
 
-    fn update(&mut self) {
-        for cart in &mut self.carts {
-            cart.update_position();
-            if self.tracks.matrix.get(cart.row, cart.col) == TrackElement::FirstTurn || self.tracks.matrix.get(cart.row, cart.col) == TrackElement::SecondTurn {
-                cart.update_on_turn_type(&self.tracks.matrix.get(cart.row, cart.col));
-            } else if self.tracks.matrix.get(cart.row, cart.col) == TrackElement::Intersection {
-                cart.update_on_intersection();
-            }
-        }
+    fn subscribe<F>(&mut self, listener: F)
+        where F: FnMut(&CartEvent) + 'static
+    {
+        self.listeners.push(Box::new(listener));
     }
 
-    fn update_correct(&mut self) {
-        self.carts.sort_by(|first, second| first.get_position().cmp(&second.get_position()));
-        let mut carts_to_be_deleted_ids: HashSet<usize> = HashSet::new();
+    // Carts move one at a time in reading order. A crash removes both carts right
+    // away, so a cart that is hit before its own turn does not move this tick.
+    fn tick(&mut self) -> Vec<CartEvent> {
+        self.carts.sort_by_key(|cart| cart.get_position());
+        let mut events: Vec<CartEvent> = Vec::new();
+        let mut crashed_ids: HashSet<usize> = HashSet::new();
         for cart_index in 0..self.carts.len() {
-            {
-                let cart = &mut self.carts[cart_index];
-                if !carts_to_be_deleted_ids.contains(&cart.id) {
-                    cart.update_position();
-                    if self.tracks.matrix.get(cart.row, cart.col) == TrackElement::FirstTurn || self.tracks.matrix.get(cart.row, cart.col) == TrackElement::SecondTurn {
-                        cart.update_on_turn_type(&self.tracks.matrix.get(cart.row, cart.col));
-                    } else if self.tracks.matrix.get(cart.row, cart.col) == TrackElement::Intersection {
-                        cart.update_on_intersection();
-                    }
-                }
+            if crashed_ids.contains(&self.carts[cart_index].id) {
+                continue;
             }
-            for other_cart_index in 0..self.carts.len() {
-                let cart = &self.carts[cart_index];
-                let other_cart = &self.carts[other_cart_index];
-                if cart.id != other_cart.id && cart.get_position() == other_cart.get_position() {
-                    carts_to_be_deleted_ids.insert(cart.id);
-                    carts_to_be_deleted_ids.insert(other_cart.id);
-                }
+            let cart = &mut self.carts[cart_index];
+            let from = cart.get_position();
+            cart.update_position();
+            let position = cart.get_position();
+            events.push(CartEvent::Moved { id: cart.id, from, to: position });
+            let previous_direction = cart.direction.clone();
+            match self.tracks.matrix.get(cart.row, cart.col) {
+                track_element @ (TrackElement::FirstTurn | TrackElement::SecondTurn) => cart.update_on_turn_type(&track_element),
+                TrackElement::Intersection => cart.update_on_intersection(),
+                _ => {},
+            }
+            if cart.direction != previous_direction {
+                events.push(CartEvent::Turned {
+                    id: cart.id,
+                    position,
+                    from: previous_direction,
+                    to: cart.direction.clone()
+                });
+            }
+            let moving_id = cart.id;
+            let hit_cart = self.carts.iter()
+                .find(|other| other.id != moving_id && !crashed_ids.contains(&other.id) && other.get_position() == position);
+            if let Some(hit_cart) = hit_cart {
+                let hit_id = hit_cart.id;
+                crashed_ids.insert(moving_id);
+                crashed_ids.insert(hit_id);
+                self.crash_sites.push(position);
+                events.push(CartEvent::Crashed { position, moving_id, hit_id });
             }
         }
-        self.carts = self.carts.iter()
-            .filter(|cart| !carts_to_be_deleted_ids.contains(&cart.id))
-            .map(|cart| cart.clone())
-            .collect();
+        self.carts.retain(|cart| !crashed_ids.contains(&cart.id));
+        for event in &events {
+            for listener in &mut self.listeners {
+                listener(event);
+            }
+        }
+        events
     }
 
-    fn get_crash_position(&self) -> Option<(usize, usize)> {
-        let mut result: Option<(usize, usize)> = None;
-        for first_index in 0..(self.carts.len() - 1) {
-            for second_index in (first_index + 1)..self.carts.len() {
-                let first_cart = &self.carts[first_index];
-                let second_cart = &self.carts[second_index];
-                if let None = result {
-                    if first_cart.row == second_cart.row && first_cart.col == second_cart.col {
-                        result = Some((first_cart.row, first_cart.col));
-                    }
-                }
-            }
+    fn get_repr(&self) -> String {
+        let mut chars = self.tracks.chars.clone();
+        for position in &self.crash_sites {
+            chars.set(position.row, position.col, CRASH_CHAR);
+        }
+        for cart in &self.carts {
+            chars.set(cart.row, cart.col, cart.direction.get_cart_char());
         }
-        result
+        chars.data.iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
-    fn remove_crashing_carts(&mut self) {
-        let mut crashing_carts_indicies: Vec<usize> = Vec::new();
-        for first_index in 0..(self.carts.len() - 1) {
-            for second_index in (first_index + 1)..self.carts.len() {
-                let first_cart = &self.carts[first_index];
-                let second_cart = &self.carts[second_index];
-                if first_cart.row == second_cart.row && first_cart.col == second_cart.col {
-                    crashing_carts_indicies.push(first_index);
-                    crashing_carts_indicies.push(second_index);
-                }
+    fn get_first_crash_position(&mut self) -> Option<Position> {
+        for _ in 0..MAX_TICKS {
+            let crash_position = self.tick().into_iter()
+                .find_map(|event| match event {
+                    CartEvent::Crashed { position, .. } => Some(position),
+                    _ => None,
+                });
+            if crash_position.is_some() {
+                return crash_position;
             }
         }
-        let updated_carts: Vec<_> = self.carts.iter()
-            .enumerate()
-            .filter(|&(index, _)| !crashing_carts_indicies.contains(&index))
-            .map(|(_, cart)| cart.clone())
+        None
+    }
+
+    fn get_last_remaining_cart_position(&mut self) -> Result<Position, String> {
+        let mut positions: HashMap<usize, Position> = self.carts.iter()
+            .map(|cart| (cart.id, cart.get_position()))
             .collect();
-        if self.carts != updated_carts {
-            let difference: Vec<_> = self.carts.iter()
-                .filter(|&cart| !updated_carts.contains(cart))
-                .collect();
-            println!("{:?}", difference);
-        }
-        self.carts = updated_carts;
-    } 
-
-    fn get_first_crash_position(&mut self) -> (usize, usize) {
-        let mut result: (usize, usize) = (0, 0);
-        let mut is_not_done = true;
-        while is_not_done {
-            let crash_position = self.get_crash_position();
-            if let Some(position) = crash_position {
-                result = position;
-                is_not_done = false;
-            } else {
-                self.update();
+        let mut num_ticks: usize = 0;
+        while positions.len() > 1 {
+            if num_ticks == MAX_TICKS {
+                return Err(format!("{} carts left after {} ticks", positions.len(), MAX_TICKS));
             }
+            for event in self.tick() {
+                match event {
+                    CartEvent::Moved { id, to, .. } => {
+                        positions.insert(id, to);
+                    },
+                    CartEvent::Crashed { moving_id, hit_id, .. } => {
+                        positions.remove(&moving_id);
+                        positions.remove(&hit_id);
+                    },
+                    CartEvent::Turned { .. } => {},
+                }
+            }
+            num_ticks += 1;
         }
-        result
+        positions.values().next().copied().ok_or(String::from("every cart crashed"))
     }
+}
 
-    fn get_last_remaining_cart_position(&mut self) -> (usize, usize) {
-        while self.carts.len() != 1 {
-            self.update_correct()
-        }
-        let last_cart = self.carts.first().unwrap();
-        (last_cart.row, last_cart.col)
-    }
+fn get_state(char_matrix: &Matrix<char>, config: &IntersectionConfig) -> State {
+    State::with_intersection_config(char_matrix, config).unwrap_or_else(|errors| {
+        let messages: Vec<String> = errors.iter()
+            .map(|error| error.to_string())
+            .collect();
+        panic!("Invalid tracks:\n{}", messages.join("\n"))
+    })
 }
 
 pub fn solve_first_part() {
     let char_matrix = read_matrix("day_thirteen.txt");
-    let mut state = State::new(&char_matrix);
-    let (row, col) = state.get_first_crash_position();
-    println!("{},{}", col, row);
+    let mut state = get_state(&char_matrix, &IntersectionConfig::default());
+    let position = state.get_first_crash_position().expect("No crash happened");
+    println!("{},{}", position.col, position.row);
 }
 
 pub fn solve_second_part() {
     let char_matrix = read_matrix("day_thirteen.txt");
-    let mut state = State::new(&char_matrix);
-    let (row, col) = state.get_last_remaining_cart_position();
-    println!("{},{}", col, row);
+    let mut state = get_state(&char_matrix, &IntersectionConfig::default());
+    let position = state.get_last_remaining_cart_position().unwrap_or_else(|message| panic!("No last cart: {}", message));
+    println!("{},{}", position.col, position.row);
+}
+
+pub fn solve_with_intersection_config(config_path: &str) {
+    let config = IntersectionConfig::from_lines(&read_lines(config_path))
+        .unwrap_or_else(|message| panic!("Invalid intersection config: {}", message));
+    let char_matrix = read_matrix("day_thirteen.txt");
+    match get_state(&char_matrix, &config).get_first_crash_position() {
+        Some(position) => println!("{},{}", position.col, position.row),
+        None => println!("No crash in {} ticks", MAX_TICKS),
+    }
+    let mut state = get_state(&char_matrix, &config);
+    match state.get_last_remaining_cart_position() {
+        Ok(position) => println!("{},{}", position.col, position.row),
+        Err(message) => println!("No last cart: {}", message),
+    }
+    println!("{}", state.get_repr());
+}
+
+// Logs every crash as the carts run until one is left, then how many turns were
+// taken on the way.
+pub fn solve_with_event_log() {
+    let char_matrix = read_matrix("day_thirteen.txt");
+    let mut state = get_state(&char_matrix, &IntersectionConfig::default());
+    let num_turns = Rc::new(Cell::new(0usize));
+    let listener_num_turns = Rc::clone(&num_turns);
+    state.subscribe(move |event| match event {
+        CartEvent::Crashed { position, moving_id, hit_id } => {
+            println!("cart {} hit cart {} at {},{}", moving_id, hit_id, position.col, position.row);
+        },
+        CartEvent::Turned { .. } => listener_num_turns.set(listener_num_turns.get() + 1),
+        CartEvent::Moved { .. } => {},
+    });
+    match state.get_last_remaining_cart_position() {
+        Ok(position) => println!("last cart at {},{}", position.col, position.row),
+        Err(message) => println!("No last cart: {}", message),
+    }
+    println!("{} turns", num_turns.get());
 }
@@ -1,8 +1,10 @@
+use std::hash::{Hash, Hasher};
 use std::ops::Range;
 use std::usize;
 use std::cmp::Eq;
+use std::convert::TryFrom;
 
-use regex::Regex;
+use crate::parser::{BoxedParser, Parser, integer, left, literal, match_item, pair, parse_all, right};
 use crate::utils::read_lines;
 
 struct Rectangle {
@@ -15,25 +17,25 @@ struct Rectangle {
 
 impl Rectangle {
     fn from_string(input: &str) -> Option<Rectangle> {
-        let regex = Regex::new(r"#(\d+) @ (\d+),(\d+): (\d+)x(\d+)").unwrap();
-        let capture = regex.captures(input)?;
-        let id = capture[1].parse::<i32>().ok()?;
-        let left = capture[2].parse::<i32>().ok()?;
-        let top = capture[3].parse::<i32>().ok()?;
-        let width = capture[4].parse::<i32>().ok()?;
-        let height = capture[5].parse::<i32>().ok()?;
-        Some(
-            Rectangle {
-                id,
-                top,
-                left,
-                width,
-                height
-            }
-        )
+        parse_all(match_rectangle(), input).ok()
     }
 }
 
+fn match_i32<'a>() -> BoxedParser<'a, str, i32> {
+    integer().try_map(|value| i32::try_from(value).ok(), "a 32 bit integer")
+}
+
+// #id @ left,top: widthxheight
+fn match_rectangle<'a>() -> BoxedParser<'a, str, Rectangle> {
+    pair(
+        right(match_item('#'), match_i32()),
+        pair(
+            right(literal(" @ "), pair(left(match_i32(), match_item(',')), match_i32())),
+            right(literal(": "), pair(left(match_i32(), match_item('x')), match_i32()))
+        )
+    ).map(|(id, ((left, top), (width, height)))| Rectangle { id, top, left, width, height })
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct MatrixRange {
     pub row_range: Range<usize>,
@@ -42,27 +44,29 @@ pub struct MatrixRange {
 
 impl MatrixRange {
     pub fn half_horizontal(&self) -> (MatrixRange, MatrixRange) {
+        let middle_row = self.row_range.start + self.row_range.len() / 2;
         (
             MatrixRange {
-                row_range: 0..(self.row_range.len() / 2),
+                row_range: self.row_range.start..middle_row,
                 col_range: self.col_range.clone()
             },
             MatrixRange {
-                row_range: (self.row_range.len() / 2)..(self.row_range.len()),
+                row_range: middle_row..self.row_range.end,
                 col_range: self.col_range.clone()
             }
         )
     }
 
     pub fn half_vertical(&self) -> (MatrixRange, MatrixRange) {
+        let middle_col = self.col_range.start + self.col_range.len() / 2;
         (
             MatrixRange {
                 row_range: self.row_range.clone(),
-                col_range: 0..(self.col_range.len() / 2)
+                col_range: self.col_range.start..middle_col
             },
             MatrixRange {
                 row_range: self.row_range.clone(),
-                col_range: (self.col_range.len() / 2)..(self.col_range.len())
+                col_range: middle_col..self.col_range.end
             }
         )
     }
@@ -108,6 +112,16 @@ impl<T: Eq + Clone> PartialEq for Matrix<T> {
     }
 }
 
+impl<T: Eq + Clone> Eq for Matrix<T> {}
+
+impl<T: Hash> Hash for Matrix<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rows.hash(state);
+        self.cols.hash(state);
+        self.data.hash(state);
+    }
+}
+
 impl<T: Clone + Eq> Matrix<T> {
     pub fn new(rows: usize, cols: usize, default: T) -> Matrix<T> {
         let mut data: Vec<Vec<T>> = Vec::with_capacity(rows);
@@ -1,89 +1,108 @@
-use crate::utils::read_lines;
+use crate::{automaton::{CellState, Line, RuleSet}, utils::{iterate_until_cycle, read_lines}};
 
+const PLANT_CHAR: char = '#';
+const EMPTY_POT_CHAR: char = '.';
+const WINDOW_SIZE: usize = 5;
 
+impl CellState for bool {
+    fn from_char(chr: char) -> Option<bool> {
+        match chr {
+            PLANT_CHAR => Some(true),
+            EMPTY_POT_CHAR => Some(false),
+            _ => None,
+        }
+    }
 
-struct Rule {
-    pattern: Vec<bool>,
-    result: bool
+    fn to_char(&self) -> char {
+        if *self { PLANT_CHAR } else { EMPTY_POT_CHAR }
+    }
 }
 
-impl Rule {
-    fn from_string(string: &str) -> Rule {
-        let mut pattern: Vec<bool> = Vec::new();
-        for chr in (&string[0..5]).chars() {
-            pattern.push(chr == '#');
-        }
-        let result = string.chars().last().unwrap() == '#';
-        Rule { pattern, result }
+fn get_rule_set(strings: &[String]) -> RuleSet<bool> {
+    let mut rules_string = read_lines("day_twelve_rules.txt").join("\n");
+    for string in &strings[1..] {
+        rules_string.push('\n');
+        rules_string.push_str(string);
     }
+    RuleSet::from_string(&rules_string)
+        .unwrap_or_else(|message| panic!("Invalid pot rules: {}", message))
 }
 
-fn get_pot_pattern(string: &str) -> Vec<bool> {
-    let mut pattern: Vec<bool> = Vec::new();
-    for chr in string.chars() {
-        pattern.push(chr == '#');
+// The outcome for every window of five pots, indexed by the window read as a
+// binary number with the leftmost pot as the most significant bit.
+fn get_rules_table(rule_set: &RuleSet<bool>) -> Result<[bool; 1 << WINDOW_SIZE], String> {
+    let mut table = [false; 1 << WINDOW_SIZE];
+    for (index, result) in table.iter_mut().enumerate() {
+        let window: Vec<bool> = (0..WINDOW_SIZE)
+            .map(|bit| (index >> (WINDOW_SIZE - 1 - bit)) & 1 == 1)
+            .collect();
+        *result = rule_set.get_next_window_state(&window);
+    }
+    if table[0] {
+        return Err(String::from("empty pots can not grow plants, the row would be infinite"));
     }
-    pattern
+    Ok(table)
 }
 
-fn is_pattern_matched(pattern: &[bool], rule: &Rule) -> bool {
-    pattern.iter().zip(rule.pattern.iter())
-        .all(|(&first, &second)| first == second)
+fn get_next_pattern(pattern: &Line<bool>, table: &[bool; 1 << WINDOW_SIZE]) -> Line<bool> {
+    let radius = (WINDOW_SIZE / 2) as i64;
+    let first = pattern.offset - radius;
+    let last = pattern.offset + pattern.cells.len() as i64 + radius;
+    let mut cells: Vec<bool> = Vec::with_capacity((last - first) as usize);
+    let mut index: usize = 0;
+    for position in first..last {
+        index = ((index << 1) | pattern.get(position + radius, false) as usize) & (table.len() - 1);
+        cells.push(table[index]);
+    }
+    Line::new(cells, first, false)
 }
 
-
-fn get_next_pattern(current_pattern: &Vec<bool>, rules: &Vec<Rule>) -> Vec<bool> {
-    let mut next_pattern = current_pattern.clone();
-    for index in 0..=(current_pattern.len() - 5) {
-        let pattern_slice = &current_pattern[index..(index + 5)];
-        let matching_rule = rules.iter()
-            .find(|&rule| is_pattern_matched(pattern_slice, rule));
-        if let Some(rule) = matching_rule {
-            next_pattern[index + 2] = rule.result;
-        } else {
-            next_pattern[index + 2] = false;
-        }
-    }
-    next_pattern
+fn get_pattern_score(pattern: &Line<bool>) -> i64 {
+    pattern.get_positions(true).iter().sum()
 }
 
-fn get_pattern_after_iterations(pattern: &Vec<bool>, rules: &Vec<Rule>, num_iterations: usize) -> Vec<bool> {
-    let mut current_pattern = pattern.clone();
-    for index in 0..num_iterations {
-        current_pattern.insert(0, false);
-        current_pattern.insert(0, false);
-        current_pattern.insert(0, false);
-        current_pattern.push(false);
-        current_pattern.push(false);
-        current_pattern.push(false);
-        let next_pattern = get_next_pattern(&current_pattern, rules);
-        current_pattern = next_pattern;
-        let score = get_pattern_score(&current_pattern, index + 1);
-        println!("generation={}, score={}", index + 1, score);
-    }
-    current_pattern
+// Generations are compared by shape only, their offsets being kept on the side.
+// Once a shape repeats, the row keeps repeating those shapes while drifting by a
+// constant offset every period, which gives the score of any later generation
+// directly.
+fn get_score_after_iterations(pattern: &Line<bool>, table: &[bool; 1 << WINDOW_SIZE], num_iterations: usize) -> i64 {
+    let mut offsets: Vec<i64> = vec![pattern.offset];
+    let (shapes, cycle) = iterate_until_cycle(pattern.cells.clone(), num_iterations, |cells| {
+        let current_pattern = Line::new(cells.clone(), *offsets.last().unwrap(), false);
+        let next_pattern = get_next_pattern(&current_pattern, table);
+        offsets.push(next_pattern.offset);
+        next_pattern.cells
+    });
+    let (generation, shift) = match cycle {
+        Some(cycle) => {
+            let drift = offsets[cycle.prefix_length + cycle.period] - offsets[cycle.prefix_length];
+            let remaining_iterations = num_iterations - cycle.prefix_length;
+            let shift = (remaining_iterations / cycle.period) as i64 * drift;
+            (cycle.prefix_length + remaining_iterations % cycle.period, shift)
+        },
+        None => (num_iterations, 0),
+    };
+    let pattern = Line::new(shapes[generation].clone(), offsets[generation] + shift, false);
+    get_pattern_score(&pattern)
 }
 
-fn get_pattern_score(pattern: &[bool], num_iterations: usize) -> i64 {
-    pattern.iter()
-        .enumerate()
-        .filter(|&(_, &is_alive)| is_alive)
-        .map(|(index, _)| (index as i64) - 3 * (num_iterations as i64))
-        .sum()
+fn get_answer(num_iterations: usize) -> Result<i64, String> {
+    let strings = read_lines("day_twelve.txt");
+    let table = get_rules_table(&get_rule_set(&strings))?;
+    let first_pattern = Line::from_string(&strings[0], false).ok_or("invalid initial pots")?;
+    Ok(get_score_after_iterations(&first_pattern, &table, num_iterations))
 }
 
 pub fn solve_part_one(num_iterations: usize) {
-    let strings = read_lines("day_twelve.txt");
-    let first_pattern = get_pot_pattern(&strings[0]);
-    let rules: Vec<_> = strings.iter()
-        .skip(1)
-        .map(|string| Rule::from_string(string))
-        .collect();
-    let last_pattern = get_pattern_after_iterations(&first_pattern, &rules, num_iterations);
-    let answer: i64 = last_pattern.iter()
-        .enumerate()
-        .filter(|&(_, &is_alive)| is_alive)
-        .map(|(index, _)| (index as i64) - 3 * (num_iterations as i64))
-        .sum();
-    println!("{}", answer);
-}
\ No newline at end of file
+    match get_answer(num_iterations) {
+        Ok(answer) => println!("{}", answer),
+        Err(message) => println!("Invalid pots: {}", message),
+    }
+}
+
+pub fn solve_part_two() {
+    match get_answer(50_000_000_000) {
+        Ok(answer) => println!("{}", answer),
+        Err(message) => println!("Invalid pots: {}", message),
+    }
+}
@@ -1,320 +1,122 @@
-use std::{collections::{HashMap, HashSet}, i64};
+use std::{collections::{HashMap, HashSet}, fs, time::Instant};
 
-use crate::{day_three::Matrix, utils::read_lines};
+use crate::{day_three::Matrix, utils::{get_next_random, read_lines}};
+use crate::search::{self, SearchTree, StateSpace};
+use crate::parser::{BoxedParser, ParseError, Parser, cut, either, end, lazy, left, many, match_item, none_or_one, one_or_more, pair, parse_all, right, select, sep_by};
 
-
-
-#[derive(PartialEq, Eq, Clone, Copy)]
-enum Token {
-    North,
-    East,
-    South,
-    West,
-    LeftParan,
-    RightParan,
-    Pipe
-}
-
-impl Token {
-    fn to_direction(&self) -> Option<Direction> {
-        match self {
-            &Token::North => Some(Direction::North),
-            &Token::East => Some(Direction::East),
-            &Token::South => Some(Direction::South),
-            &Token::West => Some(Direction::West),
-            _ => None,
-        }
-    }
-}
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
-enum Direction {
+pub enum Direction {
     North,
     East,
     South, 
     West
 }
-#[derive(Clone, Debug)]
-pub struct Expression {
-    path: Vec<Direction>,
-    choices: Vec<Expression>,
-    next: Option<Box<Expression>>
-}
 
-impl Expression {
-    fn from_directions(directions: &Vec<Direction>) -> Expression {
-        Expression {
-            path: directions.clone(),
-            choices: vec![],
-            next: None
+impl Direction {
+    fn from_char(chr: char) -> Option<Direction> {
+        match chr {
+            'N' => Some(Direction::North),
+            'E' => Some(Direction::East),
+            'S' => Some(Direction::South),
+            'W' => Some(Direction::West),
+            _ => None,
         }
     }
-}
-
-fn get_tokens(string: &str) -> Vec<Token> {
-    string.chars()
-        .filter_map(|chr| {
-            match chr {
-                'N' => Option::<Token>::Some(Token::North),
-                'E' => Option::<Token>::Some(Token::East),
-                'S' => Option::<Token>::Some(Token::South),
-                'W' => Option::<Token>::Some(Token::West),
-                '(' => Option::<Token>::Some(Token::LeftParan),
-                ')' => Option::<Token>::Some(Token::RightParan),
-                '|' => Option::<Token>::Some(Token::Pipe),
-                _ => Option::<Token>::None,
-            }
-        })
-        .collect()
-}
-
-type ParserResult<'a, Output> = Result<(Output, &'a[Token]), &'a[Token]>;
-
-
-trait Parser<'a, Output> {
-    fn parse(&self, tokens: &'a[Token]) -> ParserResult<'a, Output>;
-
-    fn map<F, NewOutput>(self, f: F) -> BoxedParser<'a, NewOutput> 
-        where Self: Sized + 'a,
-            Output: 'a,
-            NewOutput: 'a,
-            F: Fn(Output) -> NewOutput + 'a
-    {
-        BoxedParser::new(move |input: &'a[Token]| {
-            self.parse(input).map(|(output, remaining_inputs)|{
-                (f(output), remaining_inputs)
-            })
-        })
-    }
-}
 
-impl<'a, F, Output> Parser<'a, Output> for F 
-where 
-    F: Fn(&'a[Token]) -> ParserResult<'a, Output>
-{
-    fn parse(&self, tokens: &'a[Token]) -> ParserResult<'a, Output> {
-        self(tokens)
+    fn get_char(&self) -> char {
+        match self {
+            Direction::North => 'N',
+            Direction::East => 'E',
+            Direction::South => 'S',
+            Direction::West => 'W',
+        }
     }
-}
-
-struct BoxedParser<'a, Output> {
-    parser: Box<dyn Parser<'a, Output> + 'a>
-}
 
-impl<'a, Output> BoxedParser<'a, Output> {
-    fn new<P>(p: P) -> Self
-        where P: Parser<'a, Output> + 'a 
-    {
-        BoxedParser {
-            parser: Box::new(p)
+    fn get_offset(&self) -> (i64, i64) {
+        match self {
+            Direction::North => (-1, 0),
+            Direction::East => (0, 1),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
         }
     }
-}
 
-impl<'a, Output> Parser<'a, Output> for BoxedParser<'a, Output> {
-    fn parse(&self, tokens: &'a[Token]) -> ParserResult<'a, Output> {
-        self.parser.parse(tokens)
+    fn from_offset(offset_row: i64, offset_col: i64) -> Option<Direction> {
+        DIRECTIONS.iter()
+            .find(|direction| direction.get_offset() == (offset_row, offset_col))
+            .copied()
     }
-}
-
-fn match_token<'a>(token: Token) -> BoxedParser<'a, ()> {
-    BoxedParser::new(move |input: &'a[Token]| {
-        input.get(0).and_then(|&tk| {
-            if tk == token {
-                Some(
-                    (
-                        (), &input[1..]
-                    )
-                )
-            } else {
-                None
-            }
-        }).ok_or(input)
-    })
-}
 
-fn match_path<'a>() -> BoxedParser<'a, Vec<Direction>> {
-    BoxedParser::new(move |input: &'a[Token]| {
-        let mut index: usize = 0;
-        let mut path: Vec<Direction> = Vec::new();
-        while let Some(direction) = input.get(index).and_then(|&token| token.to_direction()) {
-            path.push(direction);
-            index += 1;
-        }
-        if index == 0 {
-            ParserResult::<'a, Vec<Direction>>::Err(input)
-        } else {
-            let remaining_input = &input[index..];
-            Result::<(Vec<Direction>, &'a[Token]), &'a[Token]>::Ok(
-                (path, remaining_input)
-            )
-        }
-    })
-}
-
-fn  one_or_more<'a, Output, P>(p: P) -> BoxedParser<'a, Vec<Output>>
-    where P: Parser<'a, Output> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        p.parse(input).and_then(|(output, remaining_input)| {
-            let mut result_outputs: Vec<Output> = Vec::new();
-            result_outputs.push(output);
-            let mut current_input = remaining_input;
-            while let Ok((other_output, other_input)) = p.parse(current_input) {
-                result_outputs.push(other_output);
-                current_input = other_input;
-            }
-            Result::<(Vec<Output>, &'a[Token]), &'a[Token]>::Ok((result_outputs, current_input))
-        })
-    })
-}
-
-fn left<'a, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, R1>
-    where P1: Parser<'a, R1> + 'a,
-        P2: Parser<'a, R2> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        if let Ok((first_result, remaining_input)) = p1.parse(input) {
-            if let Ok((_, second_remaining_input)) = p2.parse(remaining_input) {
-                ParserResult::<'a, R1>::Ok((first_result, second_remaining_input))
-            } else {
-                ParserResult::<'a, R1>::Err(remaining_input)
-            }
-        } else {
-            ParserResult::<'a, R1>::Err(input)
-        }
-    }) 
-}
-
-fn right<'a, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, R2>
-    where P1: Parser<'a, R1> + 'a,
-        P2: Parser<'a, R2> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        if let Ok((_, remaining_input)) = p1.parse(input) {
-            if let Ok((second_result, second_remaining_input)) = p2.parse(remaining_input) {
-                ParserResult::<'a, R2>::Ok((second_result, second_remaining_input))
-            } else {
-                ParserResult::<'a, R2>::Err(remaining_input)
-            }
-        } else {
-            ParserResult::<'a, R2>::Err(input)
-        }
-    }) 
-}
-
-fn pair<'a, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, (R1, R2)> 
-    where P1: Parser<'a, R1> + 'a,
-        P2: Parser<'a, R2> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        let (first_output, first_input) = p1.parse(input)?;
-        let (second_output, second_input) = p2.parse(first_input)?;
-        ParserResult::<'a, (R1, R2)>::Ok(
-            ((first_output, second_output), second_input)
-        )
-    })
-}
-
-fn either<'a, R, P>(p1: P, p2: P) -> BoxedParser<'a, R>
-    where P: Parser<'a, R> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        if let Ok((output, remaining_input)) = p1.parse(input) {
-            ParserResult::<'a, R>::Ok((output, remaining_input))
-        } else if let Ok((output, remaining_input)) = p2.parse(input) {
-            ParserResult::<'a, R>::Ok((output, remaining_input))
-        } else {
-            ParserResult::<'a, R>::Err(input)
+    fn get_opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
         }
-    })
+    }
 }
 
-fn none_or_one<'a, R, P>(p: P) -> BoxedParser<'a, Option<R>>
-    where P: Parser<'a, R> + 'a
-{
-    BoxedParser::new(move |input: &'a[Token]| {
-        if let Ok((result, next_input)) = p.parse(input) {
-            ParserResult::<'a, Option<R>>::Ok((Some(result), next_input))
-        } else {
-            ParserResult::<'a, Option<R>>::Ok((None, input))
-        }
-    })
+// An expression is a sequence of terms. Every alternative of a choice is an
+// expression of its own, so an empty alternative like the one in `(NEWS|)` is an
+// expression without terms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression {
+    terms: Vec<Term>
 }
 
-fn match_expression_with_or<'a>() -> BoxedParser<'a, Expression> {
-    left(match_expression(), match_token(Token::Pipe))
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Path(Vec<Direction>),
+    Choice(Vec<Expression>)
 }
 
-fn match_inside_paranthesis<'a>() -> BoxedParser<'a, Vec<Expression>> {
-    pair(
-        one_or_more(match_expression_with_or()), 
-        none_or_one(match_expression())
-    ).map(|(vec_expressions, opt_expression)| {
-        let mut res_vec_expressions = vec_expressions.clone();
-        let end_expression = if let Some(expr) = opt_expression {
-            expr
-        } else {
-            Expression::from_directions(&vec![])
-        };
-        res_vec_expressions.push(end_expression);
-        res_vec_expressions
-    })
+fn match_path<'a>() -> BoxedParser<'a, str, Vec<Direction>> {
+    one_or_more(select(Direction::from_char, "a direction"))
 }
 
-fn match_paranthesis_expression<'a>() -> BoxedParser<'a, Expression> {
+// Everything after an opening parenthesis is committed, so a broken choice is
+// reported where it breaks instead of where the enclosing expression ends.
+fn match_choice<'a>() -> BoxedParser<'a, str, Vec<Expression>> {
     right(
-        match_token(Token::LeftParan),
-        left(
-            match_inside_paranthesis(),
-            match_token(Token::RightParan)
-        )
-    ).map(|choices| {
-        let mut expression = Expression::from_directions(&vec![]);
-        expression.choices = choices;
-        expression
-    })
-}
-
-fn match_single_path_expression<'a>() -> BoxedParser<'a, Expression> {
-    match_path().map(|directions| Expression::from_directions(&directions))
-}
-
-fn get_matched_expression<'a>(tokens: &'a[Token]) -> (Option<Expression>, &'a[Token]) {
-    if tokens.is_empty() {
-        (None, tokens)
-    } else {
-        let expression_parser = either(match_paranthesis_expression(), match_single_path_expression());
-        match expression_parser.parse(tokens) {
-            Ok((mut expression, next_tokens)) => {
-                let (opt_next_expression, next_next_tokens) = get_matched_expression(next_tokens);
-                if let Some(next_expression) = opt_next_expression {
-                    expression.next = Some(Box::new(next_expression));
-                }
-                (Some(expression), next_next_tokens)
-            },
-            Err(next_tokens) => (None, next_tokens),
-        }
-    }
+        match_item('('),
+        cut(left(
+            sep_by(lazy(match_expression), match_item('|')),
+            match_item(')').label("a direction, '(', '|' or ')'")
+        ))
+    )
+}
+
+fn match_expression<'a>() -> BoxedParser<'a, str, Expression> {
+    many(either(
+        match_path().map(Term::Path),
+        match_choice().map(Term::Choice)
+    )).map(|terms| Expression { terms })
+}
+
+// The `^` and `$` anchors are optional, but an opening anchor needs a closing one.
+fn match_route<'a>() -> BoxedParser<'a, str, Expression> {
+    pair(none_or_one(match_item('^')), match_expression())
+        .and_then(|(anchor, expression)| {
+            let closing = match anchor {
+                Some(_) => left(match_item('$').label("a direction, '(' or '$'"), end()),
+                None => end().label("a direction, '(' or end of input"),
+            };
+            closing.map(move |_| expression.clone())
+        })
 }
 
-fn match_expression<'a>() -> BoxedParser<'a, Expression> {
-    BoxedParser::new(move |input: &'a[Token]| {
-        let (expression_opt, next_input) = get_matched_expression(input);
-        if let Some(expression) = expression_opt {
-            ParserResult::<'a, Expression>::Ok((expression, next_input))
-        } else {
-            ParserResult::<'a, Expression>::Err(input)
-        }
-    })
+pub fn parse_route(string: &str) -> Result<Expression, ParseError> {
+    parse_all(match_route(), string)
 }
 
 pub fn get_expression(string: &str) -> Expression {
-    let tokens = get_tokens(string);
-    if let Ok((expression, _)) = match_expression().parse(&tokens) {
-        return expression;
+    match parse_route(string) {
+        Ok(expression) => expression,
+        Err(error) => panic!("Invalid route: {}", error),
     }
-    panic!("Error");
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -326,6 +128,16 @@ enum Tile {
 }
 
 impl Tile {
+    fn from_char(chr: char) -> Option<Tile> {
+        match chr {
+            '.' => Some(Tile::Room),
+            '#' => Some(Tile::Wall),
+            '-' => Some(Tile::DoorHorizontal),
+            '|' => Some(Tile::DoorVertical),
+            _ => None,
+        }
+    }
+
     fn get_char(&self) -> char {
         match self {
             &Tile::Room => '.',
@@ -408,62 +220,54 @@ impl Map {
         }
         matrix
     }
-}
 
-fn fill_map_by_expression(expression: &Expression, map: &mut Map, row: i64, col: i64) -> (i64, i64) {
-    let mut current_row = row;
-    let mut current_col = col;
-    for &direction in expression.path.iter() {
-        match direction {
-            Direction::North => {
-                map.set(current_row - 1, current_col, Tile::DoorHorizontal);
-                map.set(current_row - 2, current_col, Tile::Room);
-                map.set(current_row - 1, current_col - 1, Tile::Wall);
-                map.set(current_row - 1, current_col + 1, Tile::Wall);
-                current_row -= 2;
-            },
-            Direction::East => {
-                map.set(current_row, current_col + 1, Tile::DoorVertical);
-                map.set(current_row, current_col +2, Tile::Room);
-                map.set(current_row - 1, current_col + 1, Tile::Wall);
-                map.set(current_row + 1, current_col + 1, Tile::Wall);
-                current_col += 2;
-            },
-            Direction::South => {
-                map.set(current_row + 1, current_col, Tile::DoorHorizontal);
-                map.set(current_row + 2, current_col, Tile::Room);
-                map.set(current_row + 1, current_col - 1, Tile::Wall);
-                map.set(current_row + 1, current_col + 1, Tile::Wall);
-                current_row += 2;
-            },
-            Direction::West => {
-                map.set(current_row, current_col - 1, Tile::DoorVertical);
-                map.set(current_row, current_col - 2, Tile::Room);
-                map.set(current_row - 1, current_col - 1, Tile::Wall);
-                map.set(current_row + 1, current_col - 1, Tile::Wall);
-                current_col -= 2;
-            },
-        }
+    // Renders like the puzzle examples, with the starting room drawn as 'X'.
+    fn get_repr(&self) -> String {
+        let mut chars: Vec<Vec<char>> = get_matrix_repr(&self.to_matrix()).lines()
+            .map(|line| line.chars().collect())
+            .collect();
+        chars[(-self.min_row) as usize][(-self.min_col) as usize] = ORIGIN_CHAR;
+        chars.iter()
+            .map(|line| line.iter().collect::<String>() + "\n")
+            .collect()
     }
-    if !expression.choices.is_empty() {
-        let original_row = current_row;
-        let original_col = current_col;
-        for branch_expression in expression.choices.iter() {
-            let (branch_row, branch_col) = fill_map_by_expression(branch_expression, map, original_row, original_col);
-            if let Some(next_expression) = &expression.next {
-                let (next_row, next_col) = fill_map_by_expression(next_expression.as_ref(), map, branch_row, branch_col);
-                current_row = next_row;
-                current_col = next_col;
+
+    // Parses a rendering like the one of `get_repr`, which has to mark the starting
+    // room with 'X' since every coordinate is relative to it.
+    fn from_repr(string: &str) -> Result<Map, String> {
+        let lines: Vec<&str> = string.lines().collect();
+        let (origin_row, origin_col) = lines.iter().enumerate()
+            .find_map(|(row, line)| line.find(ORIGIN_CHAR).map(|col| (row as i64, col as i64)))
+            .ok_or_else(|| format!("No starting room '{}'", ORIGIN_CHAR))?;
+        let mut map = Map::new();
+        for (row, line) in lines.iter().enumerate() {
+            for (col, chr) in line.chars().enumerate() {
+                let tile = match chr {
+                    ORIGIN_CHAR => Tile::Room,
+                    chr => Tile::from_char(chr).ok_or_else(|| format!("{},{}: unknown tile '{}'", row, col, chr))?,
+                };
+                let map_row = row as i64 - origin_row;
+                let map_col = col as i64 - origin_col;
+                let is_room_position = map_row % 2 == 0 && map_col % 2 == 0;
+                let is_door_position = (map_row + map_col) % 2 != 0;
+                if (tile.is_room() && !is_room_position) || (tile.is_door() && !is_door_position) {
+                    return Err(format!("{},{}: '{}' is out of the room grid", row, col, chr));
+                }
+                map.set(map_row, map_col, tile);
             }
         }
-    } else {
-        if let Some(next_expression) = &expression.next {
-            let (next_row, next_col) = fill_map_by_expression(next_expression.as_ref(), map, current_row, current_col);
-            current_row = next_row;
-            current_col = next_col;
-        }
+        Ok(map)
+    }
+
+    fn has_same_rooms_and_doors(&self, other: &Map) -> bool {
+        let get_openings = |map: &Map| -> HashMap<(i64, i64), Tile> {
+            map.tiles.iter()
+                .filter(|(_, &tile)| tile != Tile::Wall)
+                .map(|(&position, &tile)| (position, tile))
+                .collect()
+        };
+        get_openings(self) == get_openings(other)
     }
-    (current_row, current_col)
 }
 
 fn get_matrix_repr(matrix: &Matrix<Tile>) -> String {
@@ -477,349 +281,327 @@ fn get_matrix_repr(matrix: &Matrix<Tile>) -> String {
     string
 }
 
-fn get_map_after_expression(expression: &Expression) -> (Map, Matrix<Tile>) {
+fn walk_path(directions: &[Direction], map: &mut Map, row: i64, col: i64) -> (i64, i64) {
+    let mut current_row = row;
+    let mut current_col = col;
+    for direction in directions {
+        let (offset_row, offset_col) = direction.get_offset();
+        let door_row = current_row + offset_row;
+        let door_col = current_col + offset_col;
+        let door_tile = if offset_row == 0 { Tile::DoorVertical } else { Tile::DoorHorizontal };
+        map.set(door_row, door_col, door_tile);
+        map.set(door_row + offset_col, door_col + offset_row, Tile::Wall);
+        map.set(door_row - offset_col, door_col - offset_row, Tile::Wall);
+        current_row = door_row + offset_row;
+        current_col = door_col + offset_col;
+        map.set(current_row, current_col, Tile::Room);
+    }
+    (current_row, current_col)
+}
+
+// Walks every alternative from the whole set of positions the choice can start
+// from, and continues after the choice from the union of their end positions.
+// Keeping positions in a set means rejoining branches are only walked once, which
+// keeps the walk near-linear in the route length instead of exponential in the
+// nesting.
+fn walk_expression(expression: &Expression, map: &mut Map, positions: &HashSet<(i64, i64)>) -> HashSet<(i64, i64)> {
+    let mut current_positions = positions.clone();
+    for term in expression.terms.iter() {
+        current_positions = match term {
+            Term::Path(directions) => current_positions.iter()
+                .map(|&(row, col)| walk_path(directions, map, row, col))
+                .collect(),
+            Term::Choice(alternatives) => {
+                let mut end_positions: HashSet<(i64, i64)> = HashSet::new();
+                for alternative in alternatives.iter() {
+                    end_positions.extend(walk_expression(alternative, map, &current_positions));
+                }
+                end_positions
+            },
+        };
+    }
+    current_positions
+}
+
+fn get_map(expression: &Expression) -> Map {
     let mut map = Map::new();
     map.set(0, 0, Tile::Room);
-    fill_map_by_expression(expression, &mut map, 0, 0);
-    let matrix = map.to_matrix();
-    (map, matrix)
-}
-
-const OFFSETS: &[(i64, i64); 4] = &[
-    (-1, 0),
-    (0, 1),
-    (1, 0),
-    (0, -1)
-];
-
-fn get_furthest_room(map: &Map, matrix: &Matrix<Tile>) -> usize {
-    let mut visited: HashSet<(usize, usize)> = HashSet::new();
-    let mut queue: Vec<((usize, usize), usize)> = Vec::new();
-    let mut max_dist: usize = 0;
-    let first_row = (-map.min_row) as usize;
-    let first_col = (-map.min_col) as usize;
-    visited.insert((first_row, first_col));
-    queue.push(((first_row, first_col), 0));
-    while !queue.is_empty() {
-        let ((row, col), dist) = queue.remove(0);
-        for &(offset_row, offset_col) in OFFSETS {
-            let door_row_offseted = row as i64 + offset_row;
-            let door_col_offseted = col as i64 + offset_col;
-            let room_row_offseted = row as i64 + offset_row * 2;
-            let room_col_offseted = col as i64 + offset_col * 2;
-            if door_row_offseted >= 0 && door_row_offseted < matrix.rows as i64 &&
-                door_col_offseted >= 0 && door_col_offseted < matrix.cols as i64 && 
-                room_row_offseted >= 0 && room_row_offseted < matrix.rows as i64 &&
-                room_col_offseted >= 0 && room_col_offseted < matrix.rows as i64 {
-                    let door_offseted_row = door_row_offseted as usize;
-                    let door_offseted_col = door_col_offseted as usize;
-                    let room_offseted_row = room_row_offseted as usize;
-                    let room_offseted_col = room_col_offseted as usize;
-                    if matrix.get(door_offseted_row, door_offseted_col).is_door() && matrix.get(room_offseted_row, room_offseted_col).is_room() &&
-                     !visited.contains(&(room_offseted_row, room_offseted_col)) {
-                        visited.insert((room_offseted_row, room_offseted_col));
-                        queue.push(((room_offseted_row, room_offseted_col), dist + 1));
-                        if dist + 1 > max_dist {
-                            max_dist = dist + 1;
-                        }
-                    }
+    walk_expression(expression, &mut map, &HashSet::from([(0, 0)]));
+    map
+}
+
+// Rooms are numbered by (row, col) in room steps, so the room a door leads to is one
+// direction offset away, and the starting room is the origin.
+type Room = (i64, i64);
+
+const ORIGIN: Room = (0, 0);
+
+const ORIGIN_CHAR: char = 'X';
+
+pub struct RoomGraph {
+    neighbors: HashMap<Room, Vec<(Direction, Room)>>
+}
+
+impl RoomGraph {
+    fn from_map(map: &Map) -> RoomGraph {
+        let mut neighbors: HashMap<Room, Vec<(Direction, Room)>> = HashMap::new();
+        for (&(row, col), tile) in map.tiles.iter() {
+            if !tile.is_room() {
+                continue;
+            }
+            let room = (row / 2, col / 2);
+            let room_neighbors = neighbors.entry(room).or_default();
+            for &direction in DIRECTIONS.iter() {
+                let (offset_row, offset_col) = direction.get_offset();
+                if map.tiles.get(&(row + offset_row, col + offset_col)).is_some_and(|tile| tile.is_door()) {
+                    room_neighbors.push((direction, (room.0 + offset_row, room.1 + offset_col)));
                 }
+            }
         }
+        RoomGraph { neighbors }
     }
-    max_dist
-}
-
-fn get_rooms_with_distance(map: &Map, matrix: &Matrix<Tile>, distance: usize) -> usize {
-    let mut visited: HashSet<(usize, usize)> = HashSet::new();
-    let mut queue: Vec<((usize, usize), usize)> = Vec::new();
-    let mut num_rooms: usize = 0;
-    let first_row = (-map.min_row) as usize;
-    let first_col = (-map.min_col) as usize;
-    visited.insert((first_row, first_col));
-    queue.push(((first_row, first_col), 0));
-    while !queue.is_empty() {
-        let ((row, col), dist) = queue.remove(0);
-        for &(offset_row, offset_col) in OFFSETS {
-            let door_row_offseted = row as i64 + offset_row;
-            let door_col_offseted = col as i64 + offset_col;
-            let room_row_offseted = row as i64 + offset_row * 2;
-            let room_col_offseted = col as i64 + offset_col * 2;
-            if door_row_offseted >= 0 && door_row_offseted < matrix.rows as i64 &&
-                door_col_offseted >= 0 && door_col_offseted < matrix.cols as i64 && 
-                room_row_offseted >= 0 && room_row_offseted < matrix.rows as i64 &&
-                room_col_offseted >= 0 && room_col_offseted < matrix.rows as i64 {
-                    let door_offseted_row = door_row_offseted as usize;
-                    let door_offseted_col = door_col_offseted as usize;
-                    let room_offseted_row = room_row_offseted as usize;
-                    let room_offseted_col = room_col_offseted as usize;
-                    if matrix.get(door_offseted_row, door_offseted_col).is_door() && matrix.get(room_offseted_row, room_offseted_col).is_room() &&
-                     !visited.contains(&(room_offseted_row, room_offseted_col)) {
-                        visited.insert((room_offseted_row, room_offseted_col));
-                        queue.push(((room_offseted_row, room_offseted_col), dist + 1));
-                        if dist + 1 >= distance {
-                            num_rooms += 1;
-                        }
-                    }
-                }
+
+    fn search(&self, from: Room) -> SearchTree<Room> {
+        let mut graph = self;
+        search::bfs(&mut graph, from, |_| false)
+    }
+
+    pub fn get_distances(&self, from: Room) -> HashMap<Room, usize> {
+        if !self.neighbors.contains_key(&from) {
+            return HashMap::new();
         }
+        self.search(from).distances
     }
-    num_rooms
-}
-
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum TokenParser {
-    Path {
-        directions: Vec<Direction>
-    },
-    LeftParan,
-    RightParan,
-    Pipe
-}
-
-fn is_direction(string: &str) -> bool {
-    string == "N" || string == "W" || string == "E" || string == "S"
-}
-
-fn get_direction(string: &str) -> Direction {
-    match string {
-        "N" => Direction::North,
-        "E" => Direction::East,
-        "S" => Direction::South,
-        _ => Direction::West,
-    }
-}
-
-pub fn get_parser_tokens(string: &str) -> Vec<TokenParser> {
-    let mut current_string = string;
-    let mut tokens: Vec<TokenParser> = Vec::new();
-    while !current_string.is_empty() {
-        let first = &current_string[0..1];
-        if first == "(" {
-            current_string = &current_string[1..];
-            tokens.push(TokenParser::LeftParan);
-        } else if first == ")" {
-            current_string = &current_string[1..];
-            tokens.push(TokenParser::RightParan);
-        } else if first == "|" {
-            current_string = &current_string[1..];
-            tokens.push(TokenParser::Pipe);
-        } else {
-            let mut directions: Vec<Direction> = Vec::new();
-            let mut index: usize = 0;
-            while index < current_string.len() && is_direction(&current_string[index..(index + 1)]) {
-                let direction = get_direction(&current_string[index..(index + 1)]);
-                directions.push(direction);
-                index += 1;
-            }
-            current_string = &current_string[index..];
-            tokens.push(TokenParser::Path { directions });
+
+    pub fn get_shortest_path(&self, from: Room, to: Room) -> Option<String> {
+        if !self.neighbors.contains_key(&from) || !self.neighbors.contains_key(&to) {
+            return None;
         }
+        let mut graph = self;
+        let path = search::bidirectional_bfs(&mut graph, from, to)?;
+        Some(path.windows(2)
+            .map(|rooms| Direction::from_offset(rooms[1].0 - rooms[0].0, rooms[1].1 - rooms[0].1).unwrap().get_char())
+            .collect())
     }
-    tokens
-}
 
-fn get_expression_from_parser_tokens<'a>(tokens: &'a[TokenParser]) -> (Option<Expression>, &'a[TokenParser]) {
-    if tokens.is_empty() {
-        (None, tokens)
-    } else {
-        if let TokenParser::Path { directions } = &tokens[0] {
-            let mut expression = Expression::from_directions(directions);
-            let mut remaining_tokens = &tokens[1..];
-            if let (Some(next_expression), next_tokens) = get_expression_from_parser_tokens(&tokens[1..]) {
-                expression.next = Some(Box::new(next_expression));
-                remaining_tokens = next_tokens;
+    // Follows the breadth-first tree from the starting room, taking a branch for
+    // every room with more than one child. Doors that are not in the tree close
+    // loops, and walking through them and straight back is enough to open them.
+    fn push_route(&self, string: &mut String, room: Room, tree: &SearchTree<Room>) {
+        let mut current_room = room;
+        loop {
+            let mut children: Vec<(Direction, Room)> = Vec::new();
+            for &(direction, next_room) in self.neighbors[&current_room].iter() {
+                if tree.parents.get(&next_room) == Some(&current_room) {
+                    children.push((direction, next_room));
+                } else if tree.parents.get(&current_room) != Some(&next_room) && current_room < next_room {
+                    string.push(direction.get_char());
+                    string.push(direction.get_opposite().get_char());
+                }
             }
-            (Some(expression), remaining_tokens)
-        } else {
-            if tokens[0] == TokenParser::LeftParan {
-                let mut expression = Expression {
-                    path: vec![],
-                    choices: vec![],
-                    next: None
-                };
-                let mut current_tokens = &tokens[1..];
-                let mut is_not_done = true;
-                while is_not_done {
-                    let (opt_expression, next_tokens) = get_expression_from_parser_tokens(current_tokens);
-                    let branch_expression = opt_expression.unwrap();
-                    current_tokens = next_tokens;
-                    expression.choices.push(branch_expression);
-                    if current_tokens[0] == TokenParser::RightParan {
-                        is_not_done = false;
-                    } else {
-                        current_tokens = &current_tokens[1..];
-                        if current_tokens[0] == TokenParser::RightParan {
-                            is_not_done = false;
-                            expression.choices.push(Expression::from_directions(&vec![]));
+            match children.len() {
+                0 => return,
+                1 => {
+                    let (direction, next_room) = children[0];
+                    string.push(direction.get_char());
+                    current_room = next_room;
+                },
+                _ => {
+                    string.push('(');
+                    for (index, &(direction, next_room)) in children.iter().enumerate() {
+                        if index > 0 {
+                            string.push('|');
                         }
+                        string.push(direction.get_char());
+                        self.push_route(string, next_room, tree);
                     }
+                    string.push(')');
+                    return;
+                },
+            }
+        }
+    }
+
+    pub fn get_route(&self) -> String {
+        let mut string = String::from("^");
+        if self.neighbors.contains_key(&ORIGIN) {
+            self.push_route(&mut string, ORIGIN, &self.search(ORIGIN));
+        }
+        string.push('$');
+        string
+    }
+
+    // Every door is listed once, from its northern or western room.
+    pub fn get_dot(&self) -> String {
+        let mut rooms: Vec<&Room> = self.neighbors.keys().collect();
+        rooms.sort();
+        let mut string = String::from("graph facility {\n");
+        for &&(row, col) in rooms.iter() {
+            if (row, col) == ORIGIN {
+                string.push_str(&format!("    \"{},{}\" [shape=doublecircle];\n", row, col));
+            }
+            for &(direction, (next_row, next_col)) in self.neighbors[&(row, col)].iter() {
+                if direction == Direction::East || direction == Direction::South {
+                    string.push_str(&format!("    \"{},{}\" -- \"{},{}\" [label={}];\n",
+                        row, col, next_row, next_col, direction.get_char()));
                 }
-                current_tokens = &current_tokens[1..];
-                if let (Some(next_expression), next_tokens) = get_expression_from_parser_tokens(current_tokens) {
-                    expression.next = Some(Box::new(next_expression));
-                    current_tokens = next_tokens;
-                }
-                (Some(expression), current_tokens)
-            } else {
-                (None, tokens)
             }
-        }   
+        }
+        string.push_str("}\n");
+        string
     }
 }
 
-pub fn get_expression_from_string(string: &str) -> Expression {
-    let tokens = get_parser_tokens(string);
-    let (opt_expression, _) = get_expression_from_parser_tokens(&tokens);
-    opt_expression.unwrap()
-}
+impl StateSpace for &RoomGraph {
+    type State = Room;
 
-fn get_map_after_path(directions: &[Direction], map: &mut Map, row: i64, col: i64) -> (i64, i64) {
-    let mut current_row = row;
-    let mut current_col = col;
-    for &direction in directions {
-        match direction {
-            Direction::North => {
-                map.set(current_row - 1, current_col, Tile::DoorHorizontal);
-                map.set(current_row - 2, current_col, Tile::Room);
-                map.set(current_row - 1, current_col - 1, Tile::Wall);
-                map.set(current_row - 1, current_col + 1, Tile::Wall);
-                current_row -= 2;
-            },
-            Direction::East => {
-                map.set(current_row, current_col + 1, Tile::DoorVertical);
-                map.set(current_row, current_col +2, Tile::Room);
-                map.set(current_row - 1, current_col + 1, Tile::Wall);
-                map.set(current_row + 1, current_col + 1, Tile::Wall);
-                current_col += 2;
-            },
-            Direction::South => {
-                map.set(current_row + 1, current_col, Tile::DoorHorizontal);
-                map.set(current_row + 2, current_col, Tile::Room);
-                map.set(current_row + 1, current_col - 1, Tile::Wall);
-                map.set(current_row + 1, current_col + 1, Tile::Wall);
-                current_row += 2;
-            },
-            Direction::West => {
-                map.set(current_row, current_col - 1, Tile::DoorVertical);
-                map.set(current_row, current_col - 2, Tile::Room);
-                map.set(current_row - 1, current_col - 1, Tile::Wall);
-                map.set(current_row + 1, current_col - 1, Tile::Wall);
-                current_col -= 2;
-            },
-        }
+    fn get_neighbors(&mut self, room: &Room) -> Vec<(Room, usize)> {
+        self.neighbors.get(room)
+            .map(|neighbors| neighbors.iter().map(|&(_, next_room)| (next_room, 1)).collect())
+            .unwrap_or_default()
     }
-    (current_row, current_col)
 }
 
-fn get_right_paran_index(tokens: &[TokenParser]) -> usize {
-    let mut index = 0;
-    let mut nested_count: usize = 1;
-    while index < tokens.len() && nested_count != 0 {
-        if tokens[index] == TokenParser::LeftParan {
-            nested_count += 1;
-        } else if tokens[index] == TokenParser::RightParan {
-            nested_count -= 1;
-        }
-        if nested_count != 0 {
-            index += 1;
-        }
+fn get_room_graph() -> RoomGraph {
+    let strings = read_lines("day_twenty.txt");
+    let expression = get_expression(&strings[0]);
+    RoomGraph::from_map(&get_map(&expression))
+}
+
+pub fn solve_part_one() {
+    let distances = get_room_graph().get_distances(ORIGIN);
+    let answer = distances.values().max().unwrap();
+    println!("{:?}", answer);
+}
+
+pub fn solve_part_two() {
+    let distances = get_room_graph().get_distances(ORIGIN);
+    let answer = distances.values().filter(|&&distance| distance >= 1000).count();
+    println!("{:?}", answer);
+}
+
+pub fn solve_shortest_path(from: Room, to: Room) {
+    match get_room_graph().get_shortest_path(from, to) {
+        Some(path) => println!("{} ({} doors)", path, path.len()),
+        None => println!("No path from {:?} to {:?}", from, to),
     }
-    index + 1
 }
 
-fn get_separated_pipe_tokens<'a>(tokens: &'a[TokenParser]) -> Vec<&'a[TokenParser]> {
-    let mut pipe_indices: Vec<usize> = Vec::new();
-    let mut expressions: Vec<&'a[TokenParser]> = Vec::new();
-    let mut level: usize = 0;
-    for (token_index, token) in tokens.iter().enumerate() {
-        match token {
-            &TokenParser::LeftParan => {
-                level += 1;
-            },
-            &TokenParser::RightParan => {
-                level -= 1;
-            },
-            &TokenParser::Pipe => {
-                if level == 0 {
-                    pipe_indices.push(token_index);
-                }
-            }
-            _ => {},
+pub fn export_dot(path: &str) {
+    fs::write(path, get_room_graph().get_dot()).expect("Error writing file");
+}
+
+fn get_route_from_map(map: &Map) -> String {
+    RoomGraph::from_map(map).get_route()
+}
+
+pub fn get_route_from_repr(string: &str) -> Result<String, String> {
+    Map::from_repr(string).map(|map| get_route_from_map(&map))
+}
+
+// Rebuilds the puzzle map from its generated route and checks that nothing moved.
+pub fn solve_reverse_route() {
+    let strings = read_lines("day_twenty.txt");
+    let map = get_map(&get_expression(&strings[0]));
+    let route = get_route_from_repr(&map.get_repr()).unwrap();
+    let rebuilt_map = get_map(&get_expression(&route));
+    assert!(rebuilt_map.has_same_rooms_and_doors(&map), "The generated route builds a different map");
+    println!("{} bytes, generated {} bytes", strings[0].len(), route.len());
+}
+
+// The number of separate branches a walker would follow if it re-walked the rest of
+// the route once per alternative, or None if that does not fit in a u128.
+fn get_branch_count(expression: &Expression) -> Option<u128> {
+    expression.terms.iter()
+        .map(|term| match term {
+            Term::Path(_) => Some(1),
+            Term::Choice(alternatives) => alternatives.iter()
+                .map(get_branch_count)
+                .try_fold(0u128, |count, branch_count| count.checked_add(branch_count?)),
+        })
+        .try_fold(1u128, |count, branch_count| count.checked_mul(branch_count?))
+}
+
+struct RouteGenerator {
+    state: u64
+}
+
+impl RouteGenerator {
+    fn new(seed: u64) -> RouteGenerator {
+        RouteGenerator {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1
         }
     }
-    expressions.push(&tokens[0..pipe_indices[0]]);
-    for index in 0..(pipe_indices.len() - 1) {
-        let first_pipe_index = pipe_indices[index] + 1;
-        let second_pipe_index = pipe_indices[index + 1];
-        expressions.push(&tokens[first_pipe_index..second_pipe_index]);
+
+    fn get_random(&mut self, max: u64) -> u64 {
+        get_next_random(&mut self.state) % max
     }
-    expressions.push(&tokens[(pipe_indices[pipe_indices.len() - 1] + 1)..]);
-    expressions
-}
 
-fn get_directions_str(directions: &[Direction]) -> String {
-    let mut string = String::new();
-    for &direction in directions {
-        let chr = match direction {
-            Direction::North => 'N',
-            Direction::East => 'E',
-            Direction::South => 'S',
-            Direction::West => 'W',
-        };
-        string.push(chr);
+    fn get_path(&mut self) -> Vec<char> {
+        (0..=self.get_random(3)).map(|_| ['N', 'E', 'S', 'W'][self.get_random(4) as usize]).collect()
     }
-    string
-}
 
-fn get_map_after_token_expression<'a>(tokens: &'a[TokenParser], map: &mut Map, row: i64, col: i64) {
-    let mut current_row = row;
-    let mut current_col = col;
-    let mut coordinates: Vec<(i64, i64)> = Vec::new();
-    for token in tokens {
-        match token {
-            TokenParser::Path { directions } => {
-                let (next_row, next_col) = get_map_after_path(directions, map, current_row, current_col);
-                current_row = next_row;
-                current_col = next_col;
-            },
-            &TokenParser::LeftParan => {
-                coordinates.push((current_row, current_col));
-            },
-            &TokenParser::RightParan => {
-                let (next_row, next_col) = coordinates.pop().unwrap();
-                current_row = next_row;
-                current_col = next_col;
-            },
-            &TokenParser::Pipe => {
-                let &(next_row, next_col) = coordinates.last().unwrap();
-                current_row = next_row;
-                current_col = next_col;
+    // Goes out along a path, takes a nested detour and comes back the same way, so
+    // every alternative of a detour rejoins where the detour started.
+    fn push_loop(&mut self, string: &mut String, depth: usize) {
+        let path = self.get_path();
+        string.extend(path.iter());
+        if depth > 0 {
+            string.push('(');
+            self.push_loop(string, depth - 1);
+            string.push('|');
+            self.push_loop(string, depth - 1);
+            string.push_str("|)");
+        }
+        string.extend(path.iter().rev().map(|&chr| match chr {
+            'N' => 'S',
+            'E' => 'W',
+            'S' => 'N',
+            _ => 'E',
+        }));
+    }
+
+    // Shaped like the puzzle inputs: detours along the way and diverging branches
+    // only at the end of a route.
+    fn push_route(&mut self, string: &mut String, depth: usize) {
+        string.extend(self.get_path());
+        if depth > 0 {
+            for _ in 0..2 {
+                string.push('(');
+                self.push_loop(string, depth - 1);
+                string.push_str("|)");
+                string.extend(self.get_path());
             }
+            string.push('(');
+            self.push_route(string, depth - 1);
+            string.push('|');
+            self.push_route(string, depth - 1);
+            string.push(')');
         }
     }
 }
 
-fn get_map(tokens: &[TokenParser]) -> (Map, Matrix<Tile>) {
-    let mut map = Map::new();
-    map.set(0, 0, Tile::Room);
-    get_map_after_token_expression(tokens, &mut map, 0, 0);
-    let matrix = map.to_matrix();
-    (map, matrix)
+fn get_nested_route(seed: u64, depth: usize) -> String {
+    let mut generator = RouteGenerator::new(seed);
+    let mut string = String::from("^");
+    generator.push_route(&mut string, depth);
+    string.push('$');
+    string
 }
 
-pub fn solve_part_one() {
-    let strings = read_lines("day_twenty.txt");
-    let tokens = get_parser_tokens(&strings[0]);
-    let (map, matrix) = get_map(&tokens);
-    let answer = get_furthest_room(&map, &matrix);
-    println!("{:?}", answer);
+pub fn benchmark_nested_routes(max_depth: usize) {
+    for depth in 1..=max_depth {
+        let route = get_nested_route(depth as u64, depth);
+        let expression = get_expression(&route);
+        let start = Instant::now();
+        let map = get_map(&expression);
+        let elapsed = start.elapsed();
+        let num_rooms = map.tiles.values().filter(|tile| tile.is_room()).count();
+        let branch_count = match get_branch_count(&expression) {
+            Some(branch_count) => branch_count.to_string(),
+            None => format!("more than {}", u128::MAX),
+        };
+        println!("depth {}: {} bytes, {} branches, {} rooms, {:?}",
+            depth, route.len(), branch_count, num_rooms, elapsed);
+    }
 }
-
-pub fn solve_part_two() {
-    let strings = read_lines("day_twenty.txt");
-    let tokens = get_parser_tokens(&strings[0]);
-    let (map, matrix) = get_map(&tokens);
-    let answer = get_rooms_with_distance(&map, &matrix, 1000);
-    println!("{:?}", answer);
-}
\ No newline at end of file
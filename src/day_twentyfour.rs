@@ -1,14 +1,17 @@
-use std::collections::{HashMap, HashSet};
-
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::{collections::{HashMap, HashSet}, fmt};
 
 use crate::utils::read_lines;
 
+const IMMUNE_SYSTEM_HEADER: &str = "Immune System:";
+const INFECTION_HEADER: &str = "Infection:";
 const SEMICOLON_SPACE_SEPARATOR: &str = "; ";
 const WEAKNESSES_PREFIX: &str = "weak to ";
 const IMMUNITIES_PREFIX: &str = "immune to ";
 const COLON_SPACE_SEPARATOR: &str = ", ";
+const INPUT_PATH: &str = "day_twentyfour.txt";
+// The boost search limit used by `solve_part_two`. The puzzle armies are won with
+// boosts well below it; other armies may need `solve_part_two_with_max_boost`.
+const DEFAULT_MAX_BOOST: u64 = 1_000;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum GroupType {
@@ -16,6 +19,29 @@ enum GroupType {
     Infectious
 }
 
+impl GroupType {
+    fn from_header(string: &str) -> Option<GroupType> {
+        match string {
+            IMMUNE_SYSTEM_HEADER => Some(GroupType::Immune),
+            INFECTION_HEADER => Some(GroupType::Infectious),
+            _ => None,
+        }
+    }
+
+    fn get_header(&self) -> &'static str {
+        match self {
+            GroupType::Immune => IMMUNE_SYSTEM_HEADER,
+            GroupType::Infectious => INFECTION_HEADER,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Outcome {
+    Victory(GroupType),
+    Draw
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Group {
     id: usize,
@@ -26,87 +52,165 @@ struct Group {
     attack_type: String,
     initiative_points: u64,
     weaknesses: Vec<String>,
-    immunities: Vec<String>
+    immunities: Vec<String>,
+    immunities_first: bool
 }
 
-fn has_prefix(string: &str, prefix: &str) -> bool {
-    string.get(0..prefix.len())
-        .map_or(false, |substring| substring == prefix)
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    message: String
 }
 
-fn has_weakness_prefix(string: &str) -> bool {
-    has_prefix(string, WEAKNESSES_PREFIX)
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
 }
 
-fn has_immunities_prefix(string: &str) -> bool {
-    has_prefix(string, IMMUNITIES_PREFIX)
+struct LineParser<'a> {
+    string: &'a str,
+    position: usize
 }
 
-fn get_weaknesses_and_immunities(string: &str) -> (Vec<String>, Vec<String>) {
-    let mut weaknesses: Vec<String> = Vec::new();
-    let mut immunities: Vec<String> = Vec::new();
-    for sub_string in string.split("; ") {
-        if has_weakness_prefix(sub_string) {
-            let weaknesses_string = &sub_string[WEAKNESSES_PREFIX.len()..];
-            weaknesses = weaknesses_string.split(COLON_SPACE_SEPARATOR)
-                .map(|weakness_string| String::from(weakness_string))
-                .collect();
-        } else if has_immunities_prefix(sub_string) {
-            let immunities_string = &sub_string[IMMUNITIES_PREFIX.len()..];
-            immunities = immunities_string.split(COLON_SPACE_SEPARATOR)
-                .map(|immunity_string| String::from(immunity_string))
-                .collect();
+impl<'a> LineParser<'a> {
+    fn new(string: &'a str) -> LineParser<'a> {
+        LineParser {
+            string,
+            position: 0
+        }
+    }
+
+    fn get_rest(&self) -> &'a str {
+        &self.string[self.position..]
+    }
+
+    fn error(&self, expected: &str) -> (usize, String) {
+        let rest = self.get_rest();
+        let found = if rest.is_empty() {
+            String::from("end of line")
+        } else {
+            format!("\"{}\"", rest.chars().take(16).collect::<String>())
+        };
+        (self.position + 1, format!("expected {}, found {}", expected, found))
+    }
+
+    fn try_literal(&mut self, literal: &str) -> bool {
+        if self.get_rest().starts_with(literal) {
+            self.position += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result<(), (usize, String)> {
+        if self.try_literal(literal) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("\"{}\"", literal)))
+        }
+    }
+
+    fn take_while<P>(&mut self, predicate: P) -> &'a str
+        where P: Fn(char) -> bool
+    {
+        let rest = self.get_rest();
+        let length = rest.find(|chr: char| !predicate(chr)).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn parse_number(&mut self, name: &str) -> Result<u64, (usize, String)> {
+        let start = self.position;
+        let digits = self.take_while(|chr| chr.is_ascii_digit());
+        digits.parse::<u64>().map_err(|_| {
+            self.position = start;
+            self.error(name)
+        })
+    }
+
+    fn parse_word(&mut self, name: &str) -> Result<String, (usize, String)> {
+        let word = self.take_while(|chr| chr.is_ascii_lowercase());
+        if word.is_empty() {
+            Err(self.error(name))
+        } else {
+            Ok(String::from(word))
+        }
+    }
+
+    fn parse_words(&mut self, name: &str) -> Result<Vec<String>, (usize, String)> {
+        let mut words = vec![self.parse_word(name)?];
+        while self.try_literal(COLON_SPACE_SEPARATOR) {
+            words.push(self.parse_word(name)?);
+        }
+        Ok(words)
+    }
+
+    fn expect_end(&self) -> Result<(), (usize, String)> {
+        if self.get_rest().is_empty() {
+            Ok(())
+        } else {
+            Err(self.error("end of line"))
         }
     }
-    (weaknesses, immunities)
 }
 
 impl Group {
-    fn from_string(id: usize, group_type: GroupType, string: &str) -> Option<Group> {
-        lazy_static! {
-            static ref REGEX: Regex = Regex::new(r"^(\d+) units each with (\d+) hit points(?: \((.+)\))? with an attack that does (\d+) ([a-z]+) damage at initiative (\d+)$").unwrap();
-        }
-        let captures = REGEX.captures(string)?;
-        if captures.get(3).is_some() {
-            let num_units = captures[1].parse::<u64>().ok()?;
-            let hit_points = captures[2].parse::<u64>().ok()?;
-            let (weaknesses, immunities) = get_weaknesses_and_immunities(&captures[3]);
-            let attack_points = captures[4].parse::<u64>().ok()?;
-            let attack_type = String::from(&captures[5]);
-            let initiative_points = captures[6].parse::<u64>().ok()?;
-            Some(
-                Group {
-                    id, 
-                    group_type,
-                    num_units,
-                    hit_points,
-                    attack_points,
-                    attack_type,
-                    initiative_points,
-                    weaknesses,
-                    immunities
+    fn parse(id: usize, group_type: GroupType, string: &str) -> Result<Group, (usize, String)> {
+        let mut parser = LineParser::new(string);
+        let num_units = parser.parse_number("number of units")?;
+        parser.expect_literal(" units each with ")?;
+        let hit_points = parser.parse_number("hit points")?;
+        parser.expect_literal(" hit points")?;
+        let mut weaknesses: Option<Vec<String>> = None;
+        let mut immunities: Option<Vec<String>> = None;
+        let mut immunities_first = false;
+        if parser.try_literal(" (") {
+            loop {
+                let column = parser.position + 1;
+                if parser.try_literal(WEAKNESSES_PREFIX) {
+                    if weaknesses.is_some() {
+                        return Err((column, String::from("weaknesses listed twice")));
+                    }
+                    weaknesses = Some(parser.parse_words("damage type")?);
+                } else if parser.try_literal(IMMUNITIES_PREFIX) {
+                    if immunities.is_some() {
+                        return Err((column, String::from("immunities listed twice")));
+                    }
+                    immunities_first = weaknesses.is_none();
+                    immunities = Some(parser.parse_words("damage type")?);
+                } else {
+                    return Err(parser.error("\"weak to \" or \"immune to \""));
                 }
-            )
-        } else {
-            let num_units = captures[1].parse::<u64>().ok()?;
-            let hit_points = captures[2].parse::<u64>().ok()?;
-            let attack_points = captures[4].parse::<u64>().ok()?;
-            let attack_type = String::from(&captures[5]);
-            let initiative_points = captures[6].parse::<u64>().ok()?;
-            Some(
-                Group {
-                    id, 
-                    group_type,
-                    num_units,
-                    hit_points,
-                    attack_points,
-                    attack_type,
-                    initiative_points,
-                    weaknesses: vec![],
-                    immunities: vec![]
+                if !parser.try_literal(SEMICOLON_SPACE_SEPARATOR) {
+                    break;
                 }
-            )
+            }
+            parser.expect_literal(")")?;
         }
+        parser.expect_literal(" with an attack that does ")?;
+        let attack_points = parser.parse_number("attack damage")?;
+        parser.expect_literal(" ")?;
+        let attack_type = parser.parse_word("attack type")?;
+        parser.expect_literal(" damage at initiative ")?;
+        let initiative_points = parser.parse_number("initiative")?;
+        parser.expect_end()?;
+        Ok(
+            Group {
+                id,
+                group_type,
+                num_units,
+                hit_points,
+                attack_points,
+                attack_type,
+                initiative_points,
+                weaknesses: weaknesses.unwrap_or_default(),
+                immunities: immunities.unwrap_or_default(),
+                immunities_first
+            }
+        )
     }
 
     fn get_effective_power(&self) -> u64 {
@@ -123,13 +227,10 @@ impl Group {
         }
     }
 
-    fn deal_damage(&mut self, damage: u64) {
-        let num_dead_units = damage / self.hit_points;
-        if num_dead_units < self.num_units {
-            self.num_units -= num_dead_units;
-        } else {
-            self.num_units = 0;
-        }
+    fn deal_damage(&mut self, damage: u64) -> u64 {
+        let num_dead_units = (damage / self.hit_points).min(self.num_units);
+        self.num_units -= num_dead_units;
+        num_dead_units
     }
 
     fn is_dead(&self) -> bool {
@@ -137,27 +238,72 @@ impl Group {
     }
 }
 
-fn get_groups(strings: &[String]) -> Vec<Group> {
-    let mut groups: Vec<Group> = Vec::new();
-    let empty_separator_index = strings.iter()
-        .enumerate()
-        .find(|&(_, string)| string.is_empty())
-        .map(|(index, _)| index)
-        .unwrap();
-    let mut current_id: usize = 0;
-    for group_string in &strings[1..empty_separator_index] {
-        if let Some(group) = Group::from_string(current_id, GroupType::Immune, group_string) {
-            current_id += 1;
-            groups.push(group);
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} units each with {} hit points", self.num_units, self.hit_points)?;
+        let mut modifiers: Vec<String> = Vec::new();
+        if !self.weaknesses.is_empty() {
+            modifiers.push(format!("{}{}", WEAKNESSES_PREFIX, self.weaknesses.join(COLON_SPACE_SEPARATOR)));
         }
+        if !self.immunities.is_empty() {
+            let immunities = format!("{}{}", IMMUNITIES_PREFIX, self.immunities.join(COLON_SPACE_SEPARATOR));
+            if self.immunities_first {
+                modifiers.insert(0, immunities);
+            } else {
+                modifiers.push(immunities);
+            }
+        }
+        if !modifiers.is_empty() {
+            write!(f, " ({})", modifiers.join(SEMICOLON_SPACE_SEPARATOR))?;
+        }
+        write!(f, " with an attack that does {} {} damage at initiative {}",
+            self.attack_points, self.attack_type, self.initiative_points)
     }
-    for group_string in &strings[(empty_separator_index + 2)..] {
-        if let Some(group) = Group::from_string(current_id, GroupType::Infectious, group_string) {
-            current_id += 1;
+}
+
+// Groups are numbered in the order they appear, across both armies. Blank lines
+// between sections are optional and the armies may be listed in either order.
+fn parse_groups(strings: &[String]) -> Result<Vec<Group>, ParseError> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut current_group_type: Option<GroupType> = None;
+    for (index, string) in strings.iter().enumerate() {
+        let string = string.trim_end_matches('\r');
+        if string.is_empty() {
+            continue;
+        }
+        if let Some(group_type) = GroupType::from_header(string) {
+            current_group_type = Some(group_type);
+        } else if let Some(group_type) = current_group_type {
+            let group = Group::parse(groups.len(), group_type, string)
+                .map_err(|(column, message)| ParseError { line: index + 1, column, message })?;
             groups.push(group);
+        } else {
+            return Err(ParseError {
+                line: index + 1,
+                column: 1,
+                message: format!("expected \"{}\" or \"{}\" before the first group", IMMUNE_SYSTEM_HEADER, INFECTION_HEADER)
+            });
         }
     }
-    groups
+    Ok(groups)
+}
+
+// Writes the groups the way the puzzle input lists them: the immune system army
+// and then the infection army, each under its header with its groups in order, a
+// blank line between the two and no newline after the last group.
+fn get_groups_repr(groups: &[Group]) -> String {
+    let mut sorted_groups: Vec<&Group> = groups.iter().collect();
+    sorted_groups.sort_by_key(|group| group.id);
+    [GroupType::Immune, GroupType::Infectious].iter()
+        .map(|&group_type| {
+            let mut lines: Vec<String> = vec![String::from(group_type.get_header())];
+            lines.extend(sorted_groups.iter()
+                .filter(|group| group.group_type == group_type)
+                .map(|group| group.to_string()));
+            lines.join("\n")
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
 }
 
 fn get_target_selection(groups: &[Group]) -> HashMap<usize, usize> {
@@ -194,56 +340,82 @@ fn get_target_selection(groups: &[Group]) -> HashMap<usize, usize> {
     selection
 }
 
-fn execute_step(groups: &mut Vec<Group>) -> bool {
+#[derive(Debug, Clone)]
+struct BattleSummary {
+    outcome: Outcome,
+    boost: u64,
+    num_rounds: u64,
+    remaining_groups: Vec<Group>
+}
+
+impl BattleSummary {
+    fn get_total_num_of_units(&self) -> u64 {
+        get_total_num_of_units(&self.remaining_groups)
+    }
+}
+
+fn execute_step(groups: &mut Vec<Group>) -> u64 {
     let selection = get_target_selection(groups);
     if selection.is_empty() {
-        return true;
+        return 0;
     }
+    let mut num_killed_units: u64 = 0;
     groups.sort_by(|first, second| second.initiative_points.cmp(&first.initiative_points));
     for group_index in 0..groups.len() {
         if !groups[group_index].is_dead() {
             if let Some(&selected_target_id) = selection.get(&groups[group_index].id) {
                 let selected_group_index_option = groups.iter()
-                    .enumerate()
-                    .find(|&(_, other_group)| other_group.id == selected_target_id)
-                    .map(|(index, _)| index);
+                    .position(|other_group| other_group.id == selected_target_id);
                 if let Some(selected_group_index) = selected_group_index_option {
                     let is_alive = !groups[selected_group_index].is_dead();
                     let damage_dealt = groups[group_index].get_damage_dealt_to(&groups[selected_group_index]);
                     if is_alive {
-                        groups[selected_group_index].deal_damage(damage_dealt);
+                        num_killed_units += groups[selected_group_index].deal_damage(damage_dealt);
                     }
                 }
             }
         }
     }
     groups.retain(|group| !group.is_dead());
-    is_finished(groups)
+    num_killed_units
 }
 
-fn is_finished(groups: &[Group]) -> bool {
+fn get_winner(groups: &[Group]) -> Option<GroupType> {
     let num_immune_groups = groups.iter()
         .filter(|group| group.group_type == GroupType::Immune)
         .count();
     let num_infectious_groups = groups.iter()
         .filter(|group| group.group_type == GroupType::Infectious)
         .count();
-    num_immune_groups == 0 || num_infectious_groups == 0
-}
-
-fn execute_until_finished(original_groups: &mut Vec<Group>) {
-    let mut current_groups = original_groups.clone();
-    let mut previous_groups = original_groups.clone();
-    let mut is_finished = execute_step(&mut current_groups);
-    while !is_finished && previous_groups != current_groups {
-        previous_groups = current_groups.clone();
-        is_finished = execute_step(&mut current_groups);
+    if num_infectious_groups == 0 {
+        Some(GroupType::Immune)
+    } else if num_immune_groups == 0 {
+        Some(GroupType::Infectious)
+    } else {
+        None
     }
-    *original_groups = current_groups;
 }
 
-fn has_immune_system_won(groups: &[Group]) -> bool {
-    groups.iter().filter(|group| group.group_type == GroupType::Infectious).count() == 0
+// A round in which no unit dies leaves the groups exactly as they were, so every
+// following round would be identical as well and the battle is a draw.
+fn execute_until_finished(groups: &[Group], boost: u64) -> BattleSummary {
+    let mut current_groups = get_boosted_groups(groups, boost);
+    let mut num_rounds: u64 = 0;
+    let outcome = loop {
+        if let Some(winner) = get_winner(&current_groups) {
+            break Outcome::Victory(winner);
+        }
+        num_rounds += 1;
+        if execute_step(&mut current_groups) == 0 {
+            break Outcome::Draw;
+        }
+    };
+    BattleSummary {
+        outcome,
+        boost,
+        num_rounds,
+        remaining_groups: current_groups
+    }
 }
 
 fn get_total_num_of_units(groups: &[Group]) -> u64 {
@@ -252,44 +424,82 @@ fn get_total_num_of_units(groups: &[Group]) -> u64 {
         .sum()
 }
 
-fn get_num_immune_groups_after_victory(groups: &[Group]) -> u64 {
-    let mut current_attack_points_boost: u64 = 1;
-    let mut is_not_done = true;
-    let mut result: u64 = 0;
-    while is_not_done {
-        let mut current_groups: Vec<_> = groups.iter()
-            .map(|group| {
-                match group.group_type {
-                    GroupType::Immune => Group {
-                        attack_points: group.attack_points + current_attack_points_boost,
-                        ..group.clone()
-                    },
-                    GroupType::Infectious => group.clone(),
-                }
-            })
-            .collect();
-        execute_until_finished(&mut current_groups);
-        if has_immune_system_won(&current_groups) {
-            is_not_done = false;
-            result = get_total_num_of_units(&&current_groups);
-        } else {
-            current_attack_points_boost += 1;
-        }
+fn get_boosted_groups(groups: &[Group], boost: u64) -> Vec<Group> {
+    groups.iter()
+        .map(|group| {
+            match group.group_type {
+                GroupType::Immune => Group {
+                    attack_points: group.attack_points + boost,
+                    ..group.clone()
+                },
+                GroupType::Infectious => group.clone(),
+            }
+        })
+        .collect()
+}
+
+// The outcome is not monotonic in the boost: a bigger boost changes effective
+// powers and with them the target selection order, so it can end in a draw or a
+// loss where a smaller one won, and no boost is known past which a win becomes
+// impossible. A binary search could skip over the smallest winning boost, so every
+// boost is tried in order, up to `max_boost`, which keeps a hopeless search from
+// running millions of battles.
+fn get_minimal_winning_battle(groups: &[Group], max_boost: u64) -> Option<BattleSummary> {
+    (0..=max_boost)
+        .map(|boost| execute_until_finished(groups, boost))
+        .find(|summary| summary.outcome == Outcome::Victory(GroupType::Immune))
+}
+
+fn read_groups(strings: &[String]) -> Option<Vec<Group>> {
+    match parse_groups(strings) {
+        Ok(groups) => Some(groups),
+        Err(error) => {
+            println!("Invalid armies description at {}", error);
+            None
+        },
     }
-    result
 }
 
 pub fn solve_part_one() {
-    let strings = read_lines("day_twentyfour.txt");
-    let mut groups = get_groups(&strings);
-    execute_until_finished(&mut groups);
-    let answer = get_total_num_of_units(&groups);
+    let Some(groups) = read_groups(&read_lines(INPUT_PATH)) else {
+        return;
+    };
+    let summary = execute_until_finished(&groups, 0);
+    let answer = summary.get_total_num_of_units();
     println!("{}", answer);
 }
 
 pub fn solve_part_two() {
-    let strings = read_lines("day_twentyfour.txt");
-    let groups = get_groups(&strings);
-    let answer = get_num_immune_groups_after_victory(&groups);
-    println!("{}", answer);
-}
\ No newline at end of file
+    solve_part_two_with_max_boost(DEFAULT_MAX_BOOST);
+}
+
+pub fn solve_part_two_with_max_boost(max_boost: u64) {
+    let Some(groups) = read_groups(&read_lines(INPUT_PATH)) else {
+        return;
+    };
+    match get_minimal_winning_battle(&groups, max_boost) {
+        Some(summary) => {
+            println!("{}", summary.get_total_num_of_units());
+            println!("boost: {}, rounds: {}, groups left: {}", summary.boost, summary.num_rounds, summary.remaining_groups.len());
+        },
+        None => println!("The immune system can not win with a boost up to {}", max_boost),
+    }
+}
+
+// Writes the armies back out, after checking that the text parses into the same
+// groups, and tells whether it is the input text itself.
+pub fn solve_normalized_armies() {
+    let strings = read_lines(INPUT_PATH);
+    let Some(groups) = read_groups(&strings) else {
+        return;
+    };
+    let repr = get_groups_repr(&groups);
+    let repr_strings: Vec<String> = repr.split('\n').map(String::from).collect();
+    assert_eq!(parse_groups(&repr_strings), Ok(groups), "The written armies do not parse back into the same groups");
+    println!("{}", repr);
+    if repr_strings == strings {
+        println!("The written armies match the input");
+    } else {
+        println!("The written armies differ from the input");
+    }
+}
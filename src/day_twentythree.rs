@@ -1,9 +1,7 @@
-use core::f64;
-use std::{cmp::{Ordering, max}, collections::{BinaryHeap, HashMap}, u64};
-
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::{cell::Cell, cmp::{Reverse, max}, convert::TryFrom};
 
+use crate::parser::{BoxedParser, Parser, integer, left, literal, match_item, pair, parse_all, right};
+use crate::search::{self, StateSpace};
 use crate::utils::read_lines;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -31,7 +29,7 @@ struct Nanobot {
     radius: u64
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 struct Space {
     x_min: i64,
     x_max: i64,
@@ -41,51 +39,36 @@ struct Space {
     z_max: i64
 }
 
-impl Space {
-    fn new() -> Space {
-        Space {
-            x_min: i64::MAX,
-            x_max: i64::MIN,
-            y_min: i64::MAX,
-            y_max: i64::MIN,
-            z_min: i64::MAX,
-            z_max: i64::MIN
-        }
+// Splits the inclusive range [min, max] into two non-empty halves, or keeps it
+// whole when it holds a single value.
+fn split_range(min: i64, max: i64) -> Vec<(i64, i64)> {
+    if min == max {
+        vec![(min, max)]
+    } else {
+        let mid = min + (max - min) / 2;
+        vec![(min, mid), (mid + 1, max)]
     }
+}
 
-    fn update_for_nanobots(&mut self, nanobots: &[Nanobot]) {
-        for nanobot in nanobots {
-            if self.x_min > nanobot.position.x {
-                self.x_min = nanobot.position.x;
-            }
-            if self.x_max < nanobot.position.x {
-                self.x_max = nanobot.position.x;
-            }
-            if self.y_min > nanobot.position.y {
-                self.y_min = nanobot.position.y;
-            }
-            if self.y_max < nanobot.position.y {
-                self.y_max = nanobot.position.y;
-            }
-            if self.z_min > nanobot.position.z {
-                self.z_min = nanobot.position.z;
-            }
-            if self.z_max < nanobot.position.z {
-                self.z_max = nanobot.position.z;
-            }
-        }
-        let x_dimension = self.x_max - self.x_min;
-        let y_dimension = self.y_max - self.y_min;
-        let z_dimension = self.z_max - self.z_min;
-        let max_dimension = max(x_dimension, max(y_dimension, z_dimension));
-        let power = (max_dimension as f64).log2();
-        let dimension = 2.0f64.powf(power.ceil()) as i64;
-        self.x_min = -dimension;
-        self.x_max = dimension;
-        self.y_min = -dimension;
-        self.y_max = dimension;
-        self.z_min = -dimension;
-        self.z_max = dimension;
+fn get_closest_to_zero(min: i64, max: i64) -> i64 {
+    0.max(min).min(max)
+}
+
+// Bounds are inclusive, so a space with equal bounds on every axis is a single
+// point.
+impl Space {
+    // The smallest space holding every point some nanobot reaches, which is where
+    // any point in range of at least one nanobot has to be.
+    fn from_nanobot_ranges(nanobots: &[Nanobot]) -> Option<Space> {
+        let radius = |nanobot: &Nanobot| nanobot.radius as i64;
+        Some(Space {
+            x_min: nanobots.iter().map(|nanobot| nanobot.position.x - radius(nanobot)).min()?,
+            x_max: nanobots.iter().map(|nanobot| nanobot.position.x + radius(nanobot)).max()?,
+            y_min: nanobots.iter().map(|nanobot| nanobot.position.y - radius(nanobot)).min()?,
+            y_max: nanobots.iter().map(|nanobot| nanobot.position.y + radius(nanobot)).max()?,
+            z_min: nanobots.iter().map(|nanobot| nanobot.position.z - radius(nanobot)).min()?,
+            z_max: nanobots.iter().map(|nanobot| nanobot.position.z + radius(nanobot)).max()?
+        })
     }
 
     fn get_distance_from_point(&self, position: &Vector) -> u64 {
@@ -111,31 +94,24 @@ impl Space {
         distance
     }
 
+    fn get_farthest_distance_from_point(&self, position: &Vector) -> u64 {
+        max(position.x.abs_diff(self.x_min), position.x.abs_diff(self.x_max)) +
+            max(position.y.abs_diff(self.y_min), position.y.abs_diff(self.y_max)) +
+            max(position.z.abs_diff(self.z_min), position.z.abs_diff(self.z_max))
+    }
+
     fn get_distance_to_origin(&self) -> u64 {
         self.get_distance_from_point(&Vector { x: 0, y: 0, z: 0})
     }
 
+    // The nanobot reaches some point of the space.
     fn is_in_range(&self, nanobot: &Nanobot) -> bool {
-        let mut distance: u64 = 0;
-        if nanobot.position.x < self.x_min {
-            distance += (self.x_min - nanobot.position.x) as u64;
-        }
-        if nanobot.position.x > self.x_max {
-            distance += (nanobot.position.x - self.x_max) as u64;
-        }
-        if nanobot.position.y < self.y_min {
-            distance += (self.y_min - nanobot.position.y) as u64;
-        }
-        if nanobot.position.y > self.y_max {
-            distance += (nanobot.position.y - self.y_max) as u64;
-        }
-        if nanobot.position.z < self.z_min {
-            distance += (self.z_min - nanobot.position.z) as u64;
-        }
-        if nanobot.position.z > self.z_max {
-            distance += (nanobot.position.z - self.z_max) as u64;
-        }
-        distance <= nanobot.radius
+        self.get_distance_from_point(&nanobot.position) <= nanobot.radius
+    }
+
+    // The nanobot reaches every point of the space.
+    fn is_within_range(&self, nanobot: &Nanobot) -> bool {
+        self.get_farthest_distance_from_point(&nanobot.position) <= nanobot.radius
     }
 
     fn get_num_nanobots_in_range(&self, nanobots: &[Nanobot]) -> usize {
@@ -144,142 +120,70 @@ impl Space {
             .count()
     }
 
-    fn center(&self) -> Vector {
-        let x = (self.x_max + self.x_min) / 2;
-        let y = (self.y_max + self.y_min) / 2;
-        let z = (self.z_max + self.z_min) / 2;
-        Vector { x, y, z }
-    }
-
-    fn size(&self) -> u64 {
-        let x_dimension = (self.x_max - self.x_min) as u64;
-        let y_dimension = (self.y_max - self.y_min) as u64;
-        let z_dimension = (self.z_max - self.z_min) as u64;
-        max(x_dimension, max(y_dimension, z_dimension))
+    fn get_num_nanobots_within_range(&self, nanobots: &[Nanobot]) -> usize {
+        nanobots.iter()
+            .filter(|&nanobot| self.is_within_range(nanobot))
+            .count()
     }
 
-    fn split(&self) -> Vec<Space> {
-        let x_mid = (self.x_max + self.x_min) / 2;
-        let y_mid = (self.y_max + self.y_min) / 2;
-        let z_mid = (self.z_max + self.z_min) / 2;
-        vec![
-            Space {
-                x_min: self.x_min,
-                x_max: x_mid,
-                y_min: y_mid,
-                y_max: self.y_max,
-                z_min: self.z_min,
-                z_max: z_mid
-            }, 
-            Space {
-                x_min: x_mid,
-                x_max: self.x_max,
-                y_min: y_mid,
-                y_max: self.y_max,
-                z_min: self.z_min,
-                z_max: z_mid
-            },
-            Space {
-                x_min: self.x_min,
-                x_max: x_mid,
-                y_min: self.y_min,
-                y_max: y_mid,
-                z_min: self.z_min,
-                z_max: z_mid
-            },
-            Space {
-                x_min: x_mid,
-                x_max: self.x_max,
-                y_min: self.y_min,
-                y_max: y_mid,
-                z_min: self.z_min,
-                z_max: z_mid
-            }, 
-            Space {
-                x_min: self.x_min,
-                x_max: x_mid,
-                y_min: y_mid,
-                y_max: self.y_max,
-                z_min: z_mid,
-                z_max: self.z_max
-            }, 
-            Space {
-                x_min: x_mid,
-                x_max: self.x_max,
-                y_min: y_mid,
-                y_max: self.y_max,
-                z_min: z_mid,
-                z_max: self.z_max
-            },
-            Space {
-                x_min: self.x_min,
-                x_max: x_mid,
-                y_min: self.y_min,
-                y_max: y_mid,
-                z_min: z_mid,
-                z_max: self.z_max
-            },
-            Space {
-                x_min: x_mid,
-                x_max: self.x_max,
-                y_min: self.y_min,
-                y_max: y_mid,
-                z_min: z_mid,
-                z_max: self.z_max
-            }
-        ]
+    // Three extents of up to 2^64 points do not fit in a u128 either, so the
+    // product saturates.
+    fn get_num_points(&self) -> u128 {
+        (self.x_max.abs_diff(self.x_min) as u128 + 1)
+            .saturating_mul(self.y_max.abs_diff(self.y_min) as u128 + 1)
+            .saturating_mul(self.z_max.abs_diff(self.z_min) as u128 + 1)
     }
-}
 
-impl Nanobot {
-    fn from_string(string: &str) -> Option<Nanobot> {
-        lazy_static! {
-            static ref REGEX: Regex = Regex::new(r"^pos=<(\-?\d+),(\-?\d+),(\-?\d+)>, r=(\d+)$").unwrap();
+    // Each axis can be brought closest to zero on its own, so this point is the only
+    // one of the space at its distance to the origin.
+    fn get_closest_point_to_origin(&self) -> Vector {
+        Vector {
+            x: get_closest_to_zero(self.x_min, self.x_max),
+            y: get_closest_to_zero(self.y_min, self.y_max),
+            z: get_closest_to_zero(self.z_min, self.z_max)
         }
-        let captures = REGEX.captures(string)?;
-        let x = captures.get(1).and_then(|regex_match| regex_match.as_str().parse::<i64>().ok())?;
-        let y = captures.get(2).and_then(|regex_match| regex_match.as_str().parse::<i64>().ok())?;
-        let z = captures.get(3).and_then(|regex_match| regex_match.as_str().parse::<i64>().ok())?;
-        let radius = captures.get(4).and_then(|regex_match| regex_match.as_str().parse::<u64>().ok())?;
-        Some(
-            Nanobot {
-                position: Vector { x, y, z},
-                radius
-            }
-        )
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-struct PriorityQueueSpace {
-    num_nanobots: usize,
-    space: Space
-}
+    fn get_points(&self) -> impl Iterator<Item = Vector> + '_ {
+        (self.z_min..=self.z_max).flat_map(move |z| {
+            (self.y_min..=self.y_max).flat_map(move |y| {
+                (self.x_min..=self.x_max).map(move |x| Vector { x, y, z })
+            })
+        })
+    }
 
-impl Ord for PriorityQueueSpace {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.num_nanobots.cmp(&other.num_nanobots)
-            .then(other.space.get_distance_to_origin().cmp(&self.space.get_distance_to_origin()))
+    // Halves every axis longer than one, giving up to eight disjoint spaces that
+    // cover this one exactly.
+    fn split(&self) -> Vec<Space> {
+        let mut spaces: Vec<Space> = Vec::new();
+        for &(z_min, z_max) in split_range(self.z_min, self.z_max).iter() {
+            for &(y_min, y_max) in split_range(self.y_min, self.y_max).iter() {
+                for &(x_min, x_max) in split_range(self.x_min, self.x_max).iter() {
+                    spaces.push(Space { x_min, x_max, y_min, y_max, z_min, z_max });
+                }
+            }
+        }
+        spaces
     }
 }
 
-impl PartialOrd for PriorityQueueSpace {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Nanobot {
+    fn from_string(string: &str) -> Option<Nanobot> {
+        parse_all(match_nanobot(), string).ok()
     }
 }
 
-fn get_num_of_nanobots_in_range_of(position: &Vector, nanobots: &[Nanobot]) -> usize {
-    nanobots.iter()
-        .filter_map(|nanobot| {
-            let distance = position.get_manhattan_distance(&nanobot.position);
-            if distance <= nanobot.radius {
-                Some(distance)
-            } else {
-                None
-            }
-        })
-        .count()
+// pos=<x,y,z>, r=radius
+fn match_nanobot<'a>() -> BoxedParser<'a, str, Nanobot> {
+    let position = pair(
+        left(integer(), match_item(',')),
+        pair(left(integer(), match_item(',')), integer())
+    ).map(|(x, (y, z))| Vector { x, y, z });
+    let radius = integer().try_map(|radius| u64::try_from(radius).ok(), "a radius");
+    pair(
+        right(literal("pos=<"), position),
+        right(literal(">, r="), radius)
+    ).map(|(position, radius)| Nanobot { position, radius })
 }
 
 fn get_num_nanobots_in_range(nanobots: &Vec<Nanobot>, main_nanobot: &Nanobot) -> usize {
@@ -297,37 +201,90 @@ fn get_num_nanobots_in_range_of_strongest_nanobot(nanobots: &Vec<Nanobot>) -> Op
     )
 }
 
-fn get_most_populated_coordinate(nanobots: &[Nanobot]) -> Vector {
-    let mut result = Vector { x: 0, y: 0, z: 0};
-    let mut max_num_nanobots: usize = 0;
-    let mut priority_queue: BinaryHeap<PriorityQueueSpace> = BinaryHeap::new();
-    let mut original_space = Space::new();
-    original_space.update_for_nanobots(nanobots);
-    priority_queue.push(PriorityQueueSpace { space: original_space, num_nanobots: nanobots.len() });
-    while let Some(priority_queue_space) = priority_queue.pop() {
-        if priority_queue_space.space.size() == 1 {
-            let space = &priority_queue_space.space;
-            for z in space.z_min..=space.z_max {
-                for y in space.y_min..=space.y_max {
-                    for x in space.x_min..=space.x_max {
-                        let vector = Vector { x, y, z};
-                        let num_nanobots = get_num_of_nanobots_in_range_of(&vector, nanobots);
-                        if num_nanobots > max_num_nanobots {
-                            max_num_nanobots = num_nanobots;
-                            result = vector;
-                        }
-                    }
-                }
-            }
-            break;
-        } else if priority_queue_space.space.size() > 1 {
-            for space in priority_queue_space.space.split() {
-                let num_nanobots = space.get_num_nanobots_in_range(nanobots);
-                priority_queue.push(PriorityQueueSpace { space, num_nanobots });
+struct TeleportSolution {
+    num_nanobots: usize,
+    closest_point: Vector,
+    // Disjoint spaces whose points are exactly those in range of `num_nanobots`
+    // nanobots.
+    optimal_spaces: Vec<Space>
+}
+
+impl TeleportSolution {
+    fn get_num_optimal_points(&self) -> u128 {
+        self.optimal_spaces.iter()
+            .map(|space| space.get_num_points())
+            .fold(0, u128::saturating_add)
+    }
+
+    fn get_optimal_points(&self) -> impl Iterator<Item = Vector> + '_ {
+        self.optimal_spaces.iter().flat_map(|space| space.get_points())
+    }
+}
+
+// Spaces paired with the number of nanobots reaching some point of them, which
+// bounds the count of every point in the space from above. Expanding a space
+// settles it when the nanobots reaching all of it, a bound from below, are just as
+// many, and splits it otherwise, dropping the halves whose upper bound falls short
+// of a count already guaranteed somewhere.
+struct TeleportSpaces<'a> {
+    nanobots: &'a [Nanobot],
+    best_lower_bound: &'a Cell<usize>,
+    num_nanobots: usize,
+    optimal_spaces: Vec<Space>
+}
+
+impl StateSpace for TeleportSpaces<'_> {
+    type State = (Space, usize);
+
+    fn get_neighbors(&mut self, &(space, upper_bound): &(Space, usize)) -> Vec<((Space, usize), usize)> {
+        let lower_bound = space.get_num_nanobots_within_range(self.nanobots);
+        let best_lower_bound = self.best_lower_bound.get().max(lower_bound);
+        self.best_lower_bound.set(best_lower_bound);
+        if lower_bound == upper_bound {
+            if upper_bound > self.num_nanobots {
+                self.num_nanobots = upper_bound;
+                self.optimal_spaces.clear();
             }
+            self.optimal_spaces.push(space);
+            return Vec::new();
         }
+        space.split().into_iter()
+            .map(|subspace| (subspace, subspace.get_num_nanobots_in_range(self.nanobots)))
+            .filter(|&(_, subspace_upper_bound)| subspace_upper_bound >= best_lower_bound)
+            .map(|subspace| (subspace, 1))
+            .collect()
     }
-    result
+}
+
+// Branch and bound over spaces, taken off the queue by upper bound, so once it
+// falls below the best count every optimal point has been found. Ties on the
+// closest point are broken by coordinates, to keep the answer independent of the
+// search order.
+// The optimal points are kept as boxes, and a box is only settled once every point
+// in it is in range of the same nanobots. A tie region bounded by the diagonal
+// faces of the nanobot ranges is therefore covered by a number of boxes that grows
+// with its surface, down to single points along its edges, so inputs where many
+// points tie are slow, though the answer stays exact.
+fn get_teleport_solution(nanobots: &[Nanobot]) -> Option<TeleportSolution> {
+    let original_space = Space::from_nanobot_ranges(nanobots)?;
+    let best_lower_bound: Cell<usize> = Cell::new(0);
+    let mut teleport_spaces = TeleportSpaces {
+        nanobots,
+        best_lower_bound: &best_lower_bound,
+        num_nanobots: 0,
+        optimal_spaces: Vec::new()
+    };
+    search::best_first(
+        &mut teleport_spaces,
+        (original_space, nanobots.len()),
+        |&(space, upper_bound)| (upper_bound, Reverse(space.get_distance_to_origin())),
+        |&(_, upper_bound)| upper_bound < best_lower_bound.get()
+    );
+    let TeleportSpaces { num_nanobots, optimal_spaces, .. } = teleport_spaces;
+    let closest_point = optimal_spaces.iter()
+        .map(|space| space.get_closest_point_to_origin())
+        .min_by_key(|point| (point.get_distance_to_orign(), point.x, point.y, point.z))?;
+    Some(TeleportSolution { num_nanobots, closest_point, optimal_spaces })
 }
 
 pub fn solve_part_one() {
@@ -344,7 +301,25 @@ pub fn solve_part_two() {
     let nanobots: Vec<_> = strings.iter()
         .filter_map(|string| Nanobot::from_string(string))
         .collect();
-    let coordinate = get_most_populated_coordinate(&nanobots);
-    let answer = coordinate.get_distance_to_orign();
+    let solution = get_teleport_solution(&nanobots).expect("Empty list of nanobots");
+    let answer = solution.closest_point.get_distance_to_orign();
     println!("{}", answer);
-}
\ No newline at end of file
+}
+
+pub fn solve_teleport_point() {
+    let strings = read_lines("day_twentythree.txt");
+    let nanobots: Vec<_> = strings.iter()
+        .filter_map(|string| Nanobot::from_string(string))
+        .collect();
+    let solution = get_teleport_solution(&nanobots).expect("Empty list of nanobots");
+    let point = solution.closest_point;
+    println!("{} nanobots in range of {},{},{} at distance {}",
+        solution.num_nanobots, point.x, point.y, point.z, point.get_distance_to_orign());
+    let num_optimal_points = solution.get_num_optimal_points();
+    println!("{} optimal points in {} spaces", num_optimal_points, solution.optimal_spaces.len());
+    if num_optimal_points <= 10 {
+        for point in solution.get_optimal_points() {
+            println!("{},{},{}", point.x, point.y, point.z);
+        }
+    }
+}
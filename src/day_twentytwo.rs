@@ -1,20 +1,6 @@
-use priority_queue::PriorityQueue;
+use std::{collections::HashMap, fs};
 
-use crate::day_three::Matrix;
-use std::{cmp::{Ordering, Reverse}, collections::HashSet};
-
-static ROCKY_ALLOWED_EQUIPMENT_STATE: &[EquipmentState; 2] = &[
-    EquipmentState::Torch,
-    EquipmentState::ClimbingGear
-];
-static WET_ALLOWED_EQUIPMENT_STATE: &[EquipmentState; 2] = &[
-    EquipmentState::ClimbingGear,
-    EquipmentState::Neither
-];
-static NARROW_ALLOWED_EQUIPMENT_STATE: &[EquipmentState; 2] = &[
-    EquipmentState::Torch,
-    EquipmentState::Neither
-];
+use crate::search::{self, StateSpace};
 
 const OFFSETS: &[(i64, i64); 4] = &[
     (-1, 0),
@@ -23,175 +9,272 @@ const OFFSETS: &[(i64, i64); 4] = &[
     (0, -1)
 ];
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Region {
-    Rocky,
-    Wet, 
-    Narrow
+const RULES_PATH: &str = "day_twentytwo_rules.txt";
+// How far past the target a route may go when checking that the target can be
+// reached at all. The rescue itself is not bounded.
+const ROUTE_MARGIN: usize = 100;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Tool {
+    name: String,
+    char: char
 }
 
-impl Region {
-    fn from_erosion_level(erosion_level: usize) -> Region {
-        if erosion_level % 3 == 0 {
-            Region::Rocky
-        } else if erosion_level % 3 == 1 {
-            Region::Wet
-        } else {
-            Region::Narrow
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Terrain {
+    name: String,
+    char: char,
+    risk: usize,
+    move_cost: usize,
+    tools: Vec<usize>
+}
+
+// Tools and terrains are referred to by their index in the rules.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct MazeRules {
+    tools: Vec<Tool>,
+    terrains: Vec<Terrain>,
+    switch_cost: Option<usize>,
+    switch_costs: HashMap<(usize, usize), usize>,
+    start_tool: usize,
+    target_tool: usize
+}
+
+fn parse_cost(string: &str) -> Result<usize, String> {
+    string.parse::<usize>()
+        .map_err(|_| format!("invalid cost \"{}\"", string))
+}
+
+fn get_tool_index(tools: &[Tool], name: &str) -> Result<usize, String> {
+    tools.iter()
+        .position(|tool| tool.name == name)
+        .ok_or(format!("unknown tool \"{}\"", name))
+}
+
+impl MazeRules {
+    // One entry per line:
+    //   tool <name> <char>
+    //   terrain <name> <char> risk <n> move <n> tools <tool>...
+    //   switch <n> | switch <from tool> <to tool> <n>
+    //   start <tool> | target <tool>
+    // A pair without a switch cost of its own uses the plain "switch" one, and
+    // cannot be switched between if there is none. Moves must cost something.
+    // Blank lines and lines starting with "//" are ignored.
+    fn from_string(string: &str) -> Result<MazeRules, String> {
+        let mut tools: Vec<Tool> = Vec::new();
+        let mut terrains: Vec<Terrain> = Vec::new();
+        let mut switch_cost: Option<usize> = None;
+        let mut switch_costs: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut start_tool: Option<usize> = None;
+        let mut target_tool: Option<usize> = None;
+        for (index, line) in string.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let result = match words.as_slice() {
+                ["tool", name, char] => get_char(char).and_then(|char| {
+                    if tools.iter().any(|tool| &tool.name == name) {
+                        Err(format!("duplicate tool \"{}\"", name))
+                    } else {
+                        tools.push(Tool { name: name.to_string(), char });
+                        Ok(())
+                    }
+                }),
+                ["terrain", name, char, "risk", risk, "move", move_cost, "tools", terrain_tools @ ..] => {
+                    get_char(char).and_then(|char| {
+                        if terrains.iter().any(|terrain| &terrain.name == name) {
+                            return Err(format!("duplicate terrain \"{}\"", name));
+                        }
+                        let risk = risk.parse::<usize>()
+                            .map_err(|_| format!("invalid risk \"{}\"", risk))?;
+                        let move_cost = parse_cost(move_cost)?;
+                        if move_cost == 0 {
+                            return Err(String::from("the move cost must be positive"));
+                        }
+                        let terrain_tools = terrain_tools.iter()
+                            .map(|name| get_tool_index(&tools, name))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        terrains.push(Terrain { name: name.to_string(), char, risk, move_cost, tools: terrain_tools });
+                        Ok(())
+                    })
+                },
+                ["switch", cost] => parse_cost(cost).map(|cost| switch_cost = Some(cost)),
+                ["switch", from, to, cost] => get_tool_index(&tools, from).and_then(|from| {
+                    let to = get_tool_index(&tools, to)?;
+                    switch_costs.insert((from, to), parse_cost(cost)?);
+                    Ok(())
+                }),
+                ["start", name] => get_tool_index(&tools, name).map(|tool| start_tool = Some(tool)),
+                ["target", name] => get_tool_index(&tools, name).map(|tool| target_tool = Some(tool)),
+                _ => Err(String::from("expected a tool, terrain, switch, start or target entry")),
+            };
+            result.map_err(|message| format!("line {}: {}", index + 1, message))?;
+        }
+        if terrains.is_empty() {
+            return Err(String::from("no terrains"));
         }
+        Ok(MazeRules {
+            tools,
+            terrains,
+            switch_cost,
+            switch_costs,
+            start_tool: start_tool.ok_or("missing start tool")?,
+            target_tool: target_tool.ok_or("missing target tool")?
+        })
     }
 
-    fn get_risk(&self) -> usize {
-        match self {
-            &Region::Rocky => 0,
-            &Region::Wet => 1,
-            &Region::Narrow => 2,
-        }
+    fn from_file(path: &str) -> Result<MazeRules, String> {
+        let string = fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        MazeRules::from_string(&string)
+            .map_err(|message| format!("{}: {}", path, message))
     }
 
-    fn get_allowed_equipment_state(&self) -> &'static [EquipmentState] {
-        match self {
-            &Region::Rocky => ROCKY_ALLOWED_EQUIPMENT_STATE,
-            &Region::Wet => WET_ALLOWED_EQUIPMENT_STATE,
-            &Region::Narrow => NARROW_ALLOWED_EQUIPMENT_STATE,
-        }
+    fn get_terrain_index(&self, erosion_level: usize) -> usize {
+        erosion_level % self.terrains.len()
     }
 
-    fn get_char(&self) -> char {
-        match self {
-            &Region::Rocky => '.',
-            &Region::Wet => '=',
-            &Region::Narrow => '|',
-        }
+    fn get_switch_cost(&self, from: usize, to: usize) -> Option<usize> {
+        self.switch_costs.get(&(from, to)).copied().or(self.switch_cost)
+    }
+
+    fn get_min_move_cost(&self) -> usize {
+        self.terrains.iter().map(|terrain| terrain.move_cost).min().unwrap_or(0)
+    }
+
+    // The cheapest way to end up holding the target tool from any other one.
+    fn get_min_switch_cost(&self) -> usize {
+        (0..self.tools.len())
+            .filter(|&tool| tool != self.target_tool)
+            .filter_map(|tool| self.get_switch_cost(tool, self.target_tool))
+            .min()
+            .unwrap_or(0)
     }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
-enum EquipmentState {
-    Torch,
-    ClimbingGear,
-    Neither
+
+fn get_char(string: &str) -> Result<char, String> {
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+        (Some(char), None) => Ok(char),
+        _ => Err(format!("expected a single character, found \"{}\"", string)),
+    }
+}
+
+fn load_rules(path: &str) -> MazeRules {
+    MazeRules::from_file(path)
+        .unwrap_or_else(|message| panic!("Invalid maze rules: {}", message))
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
 struct ClimberState {
     row: usize,
     col: usize,
-    equipment_state: EquipmentState
+    tool: usize
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct PriorityQueueState {
-    cost: usize,
-    climber_state: ClimberState
+// Erosion levels are computed on demand and kept, so the cave has no size and
+// searches can wander as far past the target as they need.
+struct Cave {
+    depth: usize,
+    target_row: usize,
+    target_col: usize,
+    rules: MazeRules,
+    erosion_levels: HashMap<(usize, usize), usize>
 }
 
-impl Ord for PriorityQueueState {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.cost.cmp(&self.cost)
-            .then(self.climber_state.cmp(&other.climber_state))
+impl Cave {
+    fn new(depth: usize, target_row: usize, target_col: usize, rules: MazeRules) -> Cave {
+        Cave {
+            depth,
+            target_row,
+            target_col,
+            rules,
+            erosion_levels: HashMap::new()
+        }
     }
-}
 
-impl PartialOrd for PriorityQueueState {
-    fn partial_cmp(&self, other: &PriorityQueueState) -> Option<Ordering> {
-        Some(self.cmp(other))
+    // Only called once the neighbours it depends on are known.
+    fn get_geologic_index(&self, row: usize, col: usize) -> usize {
+        if (row == 0 && col == 0) || (row == self.target_row && col == self.target_col) {
+            0
+        } else if row == 0 {
+            col * 16807
+        } else if col == 0 {
+            row * 48271
+        } else {
+            self.erosion_levels[&(row - 1, col)] * self.erosion_levels[&(row, col - 1)]
+        }
     }
-}
 
-fn get_cave(rows: usize, cols: usize, depth: usize) -> Matrix<Region> {
-    let mut erosion_levels: Matrix<usize> = Matrix::new(rows, cols, 0);
-    erosion_levels.set(0, 0, depth % 20183);
-    for col in 1..cols {
-        erosion_levels.set(0, col, ((col * 16807) + depth) % 20183);
-    }
-    for row in 1..rows {
-        erosion_levels.set(row, 0, ((row * 48271) + depth) % 20183);
-    }
-    for row in 1..rows {
-        for col in 1..cols {
-            let geo_index = erosion_levels.get(row - 1, col) * erosion_levels.get(row, col - 1);
-            erosion_levels.set(row, col, (geo_index + depth) % 20183);
+    // Works through the missing neighbours with an explicit stack, since the first
+    // request far from the mouth can depend on a long chain of unknown levels.
+    fn get_erosion_level(&mut self, row: usize, col: usize) -> usize {
+        if let Some(&erosion_level) = self.erosion_levels.get(&(row, col)) {
+            return erosion_level;
         }
+        let mut pending: Vec<(usize, usize)> = vec![(row, col)];
+        while let Some(&(current_row, current_col)) = pending.last() {
+            if self.erosion_levels.contains_key(&(current_row, current_col)) {
+                pending.pop();
+                continue;
+            }
+            let mut is_ready = true;
+            if current_row > 0 && current_col > 0 {
+                for &dependency in [(current_row - 1, current_col), (current_row, current_col - 1)].iter() {
+                    if !self.erosion_levels.contains_key(&dependency) {
+                        pending.push(dependency);
+                        is_ready = false;
+                    }
+                }
+            }
+            if is_ready {
+                let erosion_level = (self.get_geologic_index(current_row, current_col) + self.depth) % 20183;
+                self.erosion_levels.insert((current_row, current_col), erosion_level);
+                pending.pop();
+            }
+        }
+        self.erosion_levels[&(row, col)]
     }
-    erosion_levels.map(Region::Rocky, |&erosion_level| {
-        Region::from_erosion_level(erosion_level)
-    })
-}
 
-fn get_erosions_and_cave(rows: usize, cols: usize, depth: usize) -> (Matrix<usize>, Matrix<Region>) {
-    let mut erosion_levels: Matrix<usize> = Matrix::new(rows, cols, 0);
-    erosion_levels.set(0, 0, depth % 20183);
-    for col in 1..cols {
-        erosion_levels.set(0, col, ((col * 16807) + depth) % 20183);
-    }
-    for row in 1..rows {
-        erosion_levels.set(row, 0, ((row * 48271) + depth) % 20183);
+    fn get_terrain_index(&mut self, row: usize, col: usize) -> usize {
+        let erosion_level = self.get_erosion_level(row, col);
+        self.rules.get_terrain_index(erosion_level)
     }
-    for row in 1..rows {
-        for col in 1..cols {
-            let geo_index = erosion_levels.get(row - 1, col) * erosion_levels.get(row, col - 1);
-            erosion_levels.set(row, col, (geo_index + depth) % 20183);
-        }
+
+    fn get_terrain(&mut self, row: usize, col: usize) -> &Terrain {
+        let terrain_index = self.get_terrain_index(row, col);
+        &self.rules.terrains[terrain_index]
     }
-    erosion_levels.set(rows - 1, cols - 1, depth % 20183);
-    let region_matrix = erosion_levels.map(Region::Rocky, |&erosion_level| {
-        Region::from_erosion_level(erosion_level)
-    });
-    (erosion_levels, region_matrix)
 }
 
-fn get_risk_level(cave: &Matrix<Region>) -> usize {
+fn get_risk_level(cave: &mut Cave) -> usize {
     let mut risk_level: usize = 0;
-    for row in 0..cave.rows {
-        for col in 0..cave.cols {
-            risk_level += cave.get(row, col).get_risk();
+    for row in 0..=cave.target_row {
+        for col in 0..=cave.target_col {
+            risk_level += cave.get_terrain(row, col).risk;
         }
     }
-    risk_level - cave.get(cave.rows - 1, cave.cols -1).get_risk()
-}
-
-fn expand_region_matrix(region_matrix: &mut Matrix<Region>, erosion_level_matrix: &mut Matrix<usize>, depth: usize) {
-    region_matrix.expand(Region::Rocky);
-    erosion_level_matrix.expand(0);
-    erosion_level_matrix.set(0, erosion_level_matrix.cols - 1, ((erosion_level_matrix.cols - 1) * 16807 + depth) % 20183);
-    erosion_level_matrix.set(erosion_level_matrix.rows - 1, 0, ((erosion_level_matrix.rows - 1) * 48271 + depth) % 20183);
-    for row in 1..erosion_level_matrix.rows {
-        let col = erosion_level_matrix.cols - 1;
-        let geo_index = erosion_level_matrix.get(row - 1, col) * erosion_level_matrix.get(row, col - 1);
-        erosion_level_matrix.set(row, col, (geo_index + depth) % 20183);
-    }
-    for col in 1..(erosion_level_matrix.cols - 1) {
-        let row = erosion_level_matrix.rows - 1;
-        let geo_index = erosion_level_matrix.get(row - 1, col) * erosion_level_matrix.get(row, col - 1);
-        erosion_level_matrix.set(row, col, (geo_index + depth) % 20183);
-    }
-    for col in 0..erosion_level_matrix.cols {
-        let row = region_matrix.rows - 1;
-        let region = Region::from_erosion_level(erosion_level_matrix.get(row, col));
-        region_matrix.set(row, col, region);
-    }
-    for row in 0..(erosion_level_matrix.rows - 1) {
-        let col = region_matrix.cols - 1;
-        let region = Region::from_erosion_level(erosion_level_matrix.get(row, col));
-        region_matrix.set(row, col, region);
-    }
+    risk_level
 }
 
-fn get_next_states(climber_state: &ClimberState, cost: usize, regions: &mut Matrix<Region>, erosion_levels: &mut Matrix<usize>, depth: usize) -> Vec<PriorityQueueState> {
-    let mut next_states: Vec<PriorityQueueState> = Vec::new();
+// Switching tools costs what the rules say for the pair, and moving costs what
+// the region moved into says, as long as it allows the current tool.
+fn get_next_states(climber_state: &ClimberState, cave: &mut Cave) -> Vec<(ClimberState, usize)> {
+    let mut next_states: Vec<(ClimberState, usize)> = Vec::new();
     let current_row = climber_state.row;
     let current_col = climber_state.col;
-    let current_region = regions.get(current_row, current_col);
-    for &equipment_state in current_region.get_allowed_equipment_state() {
-        if equipment_state != climber_state.equipment_state {
-            next_states.push(PriorityQueueState {
-                cost: cost + 7,
-                climber_state: ClimberState {
+    let current_tools = cave.get_terrain(current_row, current_col).tools.clone();
+    for tool in current_tools {
+        if tool != climber_state.tool {
+            if let Some(switch_cost) = cave.rules.get_switch_cost(climber_state.tool, tool) {
+                next_states.push((ClimberState {
                     row: climber_state.row,
                     col: climber_state.col,
-                    equipment_state
-                }
-            });
+                    tool
+                }, switch_cost));
+            }
         }
     }
     for &(offset_row, offset_col) in OFFSETS {
@@ -200,66 +283,158 @@ fn get_next_states(climber_state: &ClimberState, cost: usize, regions: &mut Matr
         if offseted_row >= 0 && offseted_col >= 0 {
             let row_offseted = offseted_row as usize;
             let col_offseted = offseted_col as usize;
-            if row_offseted >= regions.rows || col_offseted >= regions.cols {
-                expand_region_matrix(regions, erosion_levels, depth);
-            }
-            let next_region = regions.get(row_offseted, col_offseted);
-            if next_region.get_allowed_equipment_state().contains(&climber_state.equipment_state) {
-                next_states.push(PriorityQueueState {
-                    cost: cost + 1,
-                    climber_state: ClimberState {
-                        row: row_offseted,
-                        col: col_offseted,
-                        equipment_state: climber_state.equipment_state
-                    }
-                });
+            let next_terrain = cave.get_terrain(row_offseted, col_offseted);
+            if next_terrain.tools.contains(&climber_state.tool) {
+                next_states.push((ClimberState {
+                    row: row_offseted,
+                    col: col_offseted,
+                    tool: climber_state.tool
+                }, next_terrain.move_cost));
             }
         }
     }
     next_states 
 }
 
-fn is_goal(climber_state: &ClimberState, target_row: usize, target_col: usize) -> bool {
-    climber_state.equipment_state == EquipmentState::Torch && climber_state.row == target_row && 
+fn is_goal(climber_state: &ClimberState, target_row: usize, target_col: usize, target_tool: usize) -> bool {
+    climber_state.tool == target_tool && climber_state.row == target_row && 
         climber_state.col == target_col
 }
 
-fn get_min_time(regions: &mut Matrix<Region>, erosion_levels: &mut Matrix<usize>, depth: usize, target_row: usize, target_col: usize) -> usize {
-    let mut visited: HashSet<ClimberState> = HashSet::new();
-    let mut frontier: PriorityQueue<ClimberState, Reverse<usize>> = PriorityQueue::new();
-    let mut result: Option<usize> = None;
-    frontier.push(ClimberState {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchStrategy {
+    Dijkstra,
+    AStar
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum RescueStep {
+    Move {
+        row: usize,
+        col: usize
+    },
+    Switch(usize)
+}
+
+struct Rescue {
+    time: usize,
+    steps: Vec<RescueStep>,
+    num_expanded: usize
+}
+
+// Every remaining region costs at least the cheapest move, and arriving without
+// the target tool costs at least one more switch, so this never overestimates.
+fn get_heuristic(climber_state: &ClimberState, cave: &Cave) -> usize {
+    let distance = (climber_state.row as i64 - cave.target_row as i64).abs() + (climber_state.col as i64 - cave.target_col as i64).abs();
+    let switch_cost = if climber_state.tool == cave.rules.target_tool { 0 } else { cave.rules.get_min_switch_cost() };
+    distance as usize * cave.rules.get_min_move_cost() + switch_cost
+}
+
+fn get_rescue_steps(path: &[ClimberState]) -> Vec<RescueStep> {
+    path.windows(2)
+        .map(|states| {
+            let (previous_state, current_state) = (&states[0], &states[1]);
+            if previous_state.row == current_state.row && previous_state.col == current_state.col {
+                RescueStep::Switch(current_state.tool)
+            } else {
+                RescueStep::Move { row: current_state.row, col: current_state.col }
+            }
+        })
+        .collect()
+}
+
+impl StateSpace for Cave {
+    type State = ClimberState;
+
+    fn get_neighbors(&mut self, climber_state: &ClimberState) -> Vec<(ClimberState, usize)> {
+        get_next_states(climber_state, self)
+    }
+
+    fn get_heuristic(&mut self, climber_state: &ClimberState) -> usize {
+        get_heuristic(climber_state, self)
+    }
+}
+
+// The part of the cave a route may use when checking that the target is reachable.
+struct BoundedCave<'a> {
+    cave: &'a mut Cave,
+    max_row: usize,
+    max_col: usize
+}
+
+impl StateSpace for BoundedCave<'_> {
+    type State = ClimberState;
+
+    fn get_neighbors(&mut self, climber_state: &ClimberState) -> Vec<(ClimberState, usize)> {
+        let (max_row, max_col) = (self.max_row, self.max_col);
+        get_next_states(climber_state, self.cave).into_iter()
+            .filter(|(next_state, _)| next_state.row <= max_row && next_state.col <= max_col)
+            .collect()
+    }
+}
+
+// The cave has no end, so a search for a target that can not be reached would
+// never stop. Some route has to be found in a bounded part of the cave first; once
+// there is one, and with every move costing something, the unbounded search only
+// looks at finitely many states.
+fn check_target_reachable(cave: &mut Cave) -> Result<(), String> {
+    let (start_tool, target_tool) = (cave.rules.start_tool, cave.rules.target_tool);
+    if !cave.get_terrain(0, 0).tools.contains(&start_tool) {
+        return Err(format!("the {} is not allowed at the mouth", cave.rules.tools[start_tool].name));
+    }
+    let (target_row, target_col) = (cave.target_row, cave.target_col);
+    if !cave.get_terrain(target_row, target_col).tools.contains(&target_tool) {
+        return Err(format!("the {} is not allowed at the target", cave.rules.tools[target_tool].name));
+    }
+    let start_state = ClimberState {
         row: 0,
         col: 0,
-        equipment_state: EquipmentState::Torch
-    }, Reverse(0));
-    while result.is_none() {
-        let (current_state, cost) = frontier.pop().unwrap();
-        if is_goal(&current_state, target_row, target_col) {
-            result = Some(cost.0);
-        } else {
-            visited.insert(current_state.clone());
-            for next_queue_states in get_next_states(&current_state, cost.0, regions, erosion_levels, depth) {
-                if !visited.contains(&next_queue_states.climber_state) {
-                    frontier.push_increase(next_queue_states.climber_state.clone(), Reverse(next_queue_states.cost));
-                }
-            }
-        }
+        tool: start_tool
+    };
+    let mut bounded_cave = BoundedCave {
+        cave,
+        max_row: target_row + ROUTE_MARGIN,
+        max_col: target_col + ROUTE_MARGIN
+    };
+    let tree = search::bfs(&mut bounded_cave, start_state, |climber_state| is_goal(climber_state, target_row, target_col, target_tool));
+    match tree.goal {
+        Some(_) => Ok(()),
+        None => Err(format!("no route reaches the target without going more than {} regions past it", ROUTE_MARGIN)),
     }
-    result.unwrap()
+}
+
+fn find_rescue(cave: &mut Cave, strategy: SearchStrategy) -> Result<Rescue, String> {
+    check_target_reachable(cave)?;
+    let start_state = ClimberState {
+        row: 0,
+        col: 0,
+        tool: cave.rules.start_tool
+    };
+    let (target_row, target_col, target_tool) = (cave.target_row, cave.target_col, cave.rules.target_tool);
+    let is_target = |climber_state: &ClimberState| is_goal(climber_state, target_row, target_col, target_tool);
+    let tree = match strategy {
+        SearchStrategy::Dijkstra => search::dijkstra(cave, start_state, is_target),
+        SearchStrategy::AStar => search::a_star(cave, start_state, is_target),
+    };
+    let path = tree.get_goal_path().expect("The target is reachable, so the search ends there");
+    Ok(Rescue {
+        time: tree.distances[path.last().unwrap()],
+        steps: get_rescue_steps(&path),
+        num_expanded: tree.num_expanded
+    })
 }
 
 pub fn solve_part_one(target_row: usize, target_col: usize, depth: usize) {
-    let cave = get_cave(target_row + 1, target_col + 1, depth);
-    let answer = get_risk_level(&cave);
+    let mut cave = Cave::new(depth, target_row, target_col, load_rules(RULES_PATH));
+    let answer = get_risk_level(&mut cave);
     println!("{}", answer);
 }
 
-fn get_cave_repr(regions: &Matrix<Region>, rows: usize, cols: usize) -> String {
+fn get_cave_repr(cave: &mut Cave, rows: usize, cols: usize) -> String {
     let mut string = String::new();
     for row in 0..rows {
         for col in 0..cols {
-            string.push(regions.get(row, col).get_char());
+            string.push(cave.get_terrain(row, col).char);
         }
         string.push('\n');
     }
@@ -267,7 +442,91 @@ fn get_cave_repr(regions: &Matrix<Region>, rows: usize, cols: usize) -> String {
 }
 
 pub fn solve_part_two(target_row: usize, target_col: usize, depth: usize) {
-    let (mut erosion_levels, mut regions) = get_erosions_and_cave(target_row + 1, target_col + 1, depth);
-    let answer = get_min_time(&mut regions, &mut erosion_levels, depth, target_row, target_col);
-    println!("{}", answer);
-}
\ No newline at end of file
+    let mut cave = Cave::new(depth, target_row, target_col, load_rules(RULES_PATH));
+    match find_rescue(&mut cave, SearchStrategy::AStar) {
+        Ok(rescue) => println!("{}", rescue.time),
+        Err(message) => println!("No rescue: {}", message),
+    }
+}
+
+// Draws the route over the cave with the tool carried out of every region, and the
+// mouth and the target as 'M' and 'T'.
+fn get_rescue_repr(cave: &mut Cave, rescue: &Rescue) -> String {
+    let mut rows = cave.target_row + 1;
+    let mut cols = cave.target_col + 1;
+    for step in rescue.steps.iter() {
+        if let &RescueStep::Move { row, col } = step {
+            rows = rows.max(row + 1);
+            cols = cols.max(col + 1);
+        }
+    }
+    let mut chars: Vec<Vec<char>> = get_cave_repr(cave, rows + 1, cols + 1).lines()
+        .map(|line| line.chars().collect())
+        .collect();
+    let mut current_row: usize = 0;
+    let mut current_col: usize = 0;
+    let mut tool = cave.rules.start_tool;
+    for &step in rescue.steps.iter() {
+        match step {
+            RescueStep::Move { row, col } => {
+                current_row = row;
+                current_col = col;
+            },
+            RescueStep::Switch(next_tool) => {
+                tool = next_tool;
+            },
+        }
+        chars[current_row][current_col] = cave.rules.tools[tool].char;
+    }
+    chars[0][0] = 'M';
+    chars[cave.target_row][cave.target_col] = 'T';
+    chars.iter()
+        .map(|line| line.iter().collect::<String>() + "\n")
+        .collect()
+}
+
+pub fn solve_rescue_route(target_row: usize, target_col: usize, depth: usize) {
+    let mut cave = Cave::new(depth, target_row, target_col, load_rules(RULES_PATH));
+    let mut last_rescue: Option<Rescue> = None;
+    for &strategy in [SearchStrategy::Dijkstra, SearchStrategy::AStar].iter() {
+        let rescue = match find_rescue(&mut cave, strategy) {
+            Ok(rescue) => rescue,
+            Err(message) => {
+                println!("No rescue: {}", message);
+                return;
+            },
+        };
+        let num_switches = rescue.steps.iter().filter(|step| matches!(step, RescueStep::Switch(_))).count();
+        println!("{:?}: {} minutes, {} moves, {} switches, {} states expanded",
+            strategy, rescue.time, rescue.steps.len() - num_switches, num_switches, rescue.num_expanded);
+        last_rescue = Some(rescue);
+    }
+    print!("{}", get_rescue_repr(&mut cave, &last_rescue.unwrap()));
+}
+
+// The number of regions of each terrain between the mouth and the target.
+fn get_terrain_counts(cave: &mut Cave) -> Vec<(String, usize)> {
+    let mut counts: Vec<usize> = vec![0; cave.rules.terrains.len()];
+    for row in 0..=cave.target_row {
+        for col in 0..=cave.target_col {
+            counts[cave.get_terrain_index(row, col)] += 1;
+        }
+    }
+    cave.rules.terrains.iter()
+        .map(|terrain| terrain.name.clone())
+        .zip(counts)
+        .collect()
+}
+
+// Solves both parts of a variant maze described by the rules at `rules_path`.
+pub fn solve_with_rules(target_row: usize, target_col: usize, depth: usize, rules_path: &str) {
+    let mut cave = Cave::new(depth, target_row, target_col, load_rules(rules_path));
+    for (name, count) in get_terrain_counts(&mut cave) {
+        println!("{}: {} regions", name, count);
+    }
+    println!("{}", get_risk_level(&mut cave));
+    match find_rescue(&mut cave, SearchStrategy::AStar) {
+        Ok(rescue) => println!("{}", rescue.time),
+        Err(message) => println!("No rescue: {}", message),
+    }
+}
@@ -22,6 +22,11 @@ mod day_twentytwo;
 mod day_twentythree;
 mod day_twentyfour;
 mod utils;
+mod automaton;
+mod ocr;
+mod summed_area;
+mod parser;
+mod search;
 
 fn main() {
     day_twentyfour::solve_part_two();
@@ -0,0 +1,236 @@
+use std::fmt;
+
+use crate::day_three::Matrix;
+
+const LIT_CHAR: char = '#';
+const DARK_CHAR: char = '.';
+
+// The 6 by 10 block font of the 2018 puzzles.
+const LARGE_FONT: &[(char, &[&str])] = &[
+    ('A', &["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+    ('B', &["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+    ('C', &[".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+    ('E', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('F', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('G', &[".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+    ('H', &["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+    ('J', &["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+    ('K', &["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+    ('L', &["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+    ('N', &["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+    ('P', &["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+    ('R', &["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+    ('X', &["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+    ('Z', &["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+];
+
+// The 4 by 6 font used by the puzzles of other years.
+const SMALL_FONT: &[(char, &[&str])] = &[
+    ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', &["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+pub trait Pixel {
+    fn is_lit(&self) -> bool;
+}
+
+impl Pixel for bool {
+    fn is_lit(&self) -> bool {
+        *self
+    }
+}
+
+impl Pixel for char {
+    fn is_lit(&self) -> bool {
+        *self != ' ' && *self != DARK_CHAR
+    }
+}
+
+type Bitmap = Vec<Vec<bool>>;
+
+fn get_bitmap_repr(bitmap: &[Vec<bool>]) -> String {
+    bitmap.iter()
+        .map(|row| row.iter().map(|&is_lit| if is_lit { LIT_CHAR } else { DARK_CHAR }).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    height: usize,
+    glyphs: Vec<(char, Bitmap)>
+}
+
+impl Font {
+    pub fn new(height: usize) -> Font {
+        Font {
+            height,
+            glyphs: Vec::new()
+        }
+    }
+
+    // Each glyph is given as rows of '#' for lit and '.' for dark pixels.
+    pub fn from_glyphs(glyphs: &[(char, &[&str])]) -> Result<Font, String> {
+        let height = glyphs.first().map_or(0, |(_, rows)| rows.len());
+        let mut font = Font::new(height);
+        for &(chr, rows) in glyphs {
+            font.add_glyph(chr, rows)?;
+        }
+        Ok(font)
+    }
+
+    pub fn add_glyph(&mut self, chr: char, rows: &[&str]) -> Result<(), String> {
+        if rows.len() != self.height {
+            return Err(format!("glyph '{}' is {} rows high instead of {}", chr, rows.len(), self.height));
+        }
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 || rows.iter().any(|row| row.chars().count() != width) {
+            return Err(format!("the rows of glyph '{}' must have the same, non zero, width", chr));
+        }
+        let bitmap: Bitmap = rows.iter()
+            .map(|row| row.chars().map(|pixel| pixel.is_lit()).collect())
+            .collect();
+        self.glyphs.push((chr, bitmap));
+        Ok(())
+    }
+
+    // The widest glyph that matches `bitmap` from `first_col` without running past
+    // `end_col`, with its width.
+    fn get_matching_glyph(&self, bitmap: &[Vec<bool>], first_col: usize, end_col: usize) -> Option<(char, usize)> {
+        self.glyphs.iter()
+            .filter(|(_, glyph)| {
+                let width = glyph[0].len();
+                first_col + width <= end_col && glyph.iter().zip(bitmap.iter()).all(|(glyph_row, row)| {
+                    glyph_row[..] == row[first_col..(first_col + width)]
+                })
+            })
+            .map(|(chr, glyph)| (*chr, glyph[0].len()))
+            .max_by_key(|&(_, width)| width)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedGlyph {
+    pub first_col: usize,
+    pub bitmap: Bitmap
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    NoText,
+    UnknownHeight(usize),
+    UnrecognizedGlyphs(Vec<UnrecognizedGlyph>)
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::NoText => write!(f, "no lit pixels"),
+            OcrError::UnknownHeight(height) => write!(f, "no font is {} pixels high", height),
+            OcrError::UnrecognizedGlyphs(glyphs) => {
+                for glyph in glyphs {
+                    writeln!(f, "unrecognized glyph at column {}:", glyph.first_col)?;
+                    writeln!(f, "{}", get_bitmap_repr(&glyph.bitmap))?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ocr {
+    fonts: Vec<Font>
+}
+
+impl Ocr {
+    pub fn new() -> Ocr {
+        Ocr {
+            fonts: vec![
+                Font::from_glyphs(LARGE_FONT).unwrap(),
+                Font::from_glyphs(SMALL_FONT).unwrap()
+            ]
+        }
+    }
+
+    pub fn register_font(&mut self, font: Font) {
+        self.fonts.push(font);
+    }
+
+    // Blank rows around the text are ignored and the font is picked by the height of
+    // what remains. Letters are split on blank columns; a run of lit columns may hold
+    // several touching letters, which are then matched from left to right.
+    pub fn recognize<T: Pixel + Clone + Eq>(&self, matrix: &Matrix<T>) -> Result<String, OcrError> {
+        let lit_rows: Vec<usize> = (0..matrix.rows)
+            .filter(|&row| (0..matrix.cols).any(|col| matrix.get_ref(row, col).is_lit()))
+            .collect();
+        let (first_row, last_row) = match (lit_rows.first(), lit_rows.last()) {
+            (Some(&first_row), Some(&last_row)) => (first_row, last_row),
+            _ => return Err(OcrError::NoText),
+        };
+        let height = last_row - first_row + 1;
+        let fonts: Vec<&Font> = self.fonts.iter()
+            .filter(|font| font.height == height)
+            .collect();
+        if fonts.is_empty() {
+            return Err(OcrError::UnknownHeight(height));
+        }
+        let bitmap: Bitmap = (first_row..=last_row)
+            .map(|row| (0..matrix.cols).map(|col| matrix.get_ref(row, col).is_lit()).collect())
+            .collect();
+        let is_blank_col = |col: usize| bitmap.iter().all(|row| !row[col]);
+        let mut text = String::new();
+        let mut unrecognized_glyphs: Vec<UnrecognizedGlyph> = Vec::new();
+        let mut col: usize = 0;
+        while col < matrix.cols {
+            if is_blank_col(col) {
+                col += 1;
+                continue;
+            }
+            let first_col = col;
+            while col < matrix.cols && !is_blank_col(col) {
+                col += 1;
+            }
+            let mut glyph_col = first_col;
+            while glyph_col < col {
+                let glyph = fonts.iter()
+                    .filter_map(|font| font.get_matching_glyph(&bitmap, glyph_col, col))
+                    .max_by_key(|&(_, width)| width);
+                match glyph {
+                    Some((chr, width)) => {
+                        text.push(chr);
+                        glyph_col += width;
+                    },
+                    None => {
+                        unrecognized_glyphs.push(UnrecognizedGlyph {
+                            first_col: glyph_col,
+                            bitmap: bitmap.iter().map(|row| row[glyph_col..col].to_vec()).collect()
+                        });
+                        glyph_col = col;
+                    },
+                }
+            }
+        }
+        if unrecognized_glyphs.is_empty() {
+            Ok(text)
+        } else {
+            Err(OcrError::UnrecognizedGlyphs(unrecognized_glyphs))
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use std::fmt;
+
+// Anything parsers can read from: a string is read char by char with byte offsets,
+// a slice of tokens is read token by token with indices as offsets.
+pub trait Source {
+    type Item: Copy + fmt::Debug;
+
+    fn get_item(&self, offset: usize) -> Option<(Self::Item, usize)>;
+    fn get_slice(&self, start: usize, end: usize) -> &Self;
+}
+
+impl Source for str {
+    type Item = char;
+
+    fn get_item(&self, offset: usize) -> Option<(char, usize)> {
+        self[offset..].chars().next().map(|chr| (chr, chr.len_utf8()))
+    }
+
+    fn get_slice(&self, start: usize, end: usize) -> &str {
+        &self[start..end]
+    }
+}
+
+impl<T: Copy + fmt::Debug> Source for [T] {
+    type Item = T;
+
+    fn get_item(&self, offset: usize) -> Option<(T, usize)> {
+        self.get(offset).map(|&item| (item, 1))
+    }
+
+    fn get_slice(&self, start: usize, end: usize) -> &[T] {
+        &self[start..end]
+    }
+}
+
+pub struct Input<'a, S: Source + ?Sized> {
+    source: &'a S,
+    pub offset: usize
+}
+
+impl<'a, S: Source + ?Sized> Clone for Input<'a, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, S: Source + ?Sized> Copy for Input<'a, S> {}
+
+impl<'a, S: Source + ?Sized> Input<'a, S> {
+    pub fn new(source: &'a S) -> Input<'a, S> {
+        Input {
+            source,
+            offset: 0
+        }
+    }
+
+    pub fn peek(&self) -> Option<S::Item> {
+        self.source.get_item(self.offset).map(|(item, _)| item)
+    }
+
+    fn next(&self) -> Option<(S::Item, Input<'a, S>)> {
+        self.source.get_item(self.offset).map(|(item, length)| {
+            (item, Input { source: self.source, offset: self.offset + length })
+        })
+    }
+
+    fn get_error(&self, expected: &str) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            expected: expected.to_string(),
+            found: self.peek().map(|item| format!("{:?}", item)),
+            is_committed: false
+        }
+    }
+}
+
+// A committed error comes from inside a `cut`, so alternatives and repetitions pass
+// it on instead of backtracking past it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub expected: String,
+    pub found: Option<String>,
+    is_committed: bool
+}
+
+impl ParseError {
+    // Keeps the error that got further, and lists everything that was expected when
+    // both stopped at the same offset.
+    fn merge(self, other: ParseError) -> ParseError {
+        if other.offset > self.offset {
+            other
+        } else if other.offset < self.offset || other.expected == self.expected {
+            self
+        } else {
+            ParseError {
+                expected: format!("{} or {}", self.expected, other.expected),
+                ..self
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(f, "offset {}: expected {}, found {}", self.offset, self.expected, found),
+            None => write!(f, "offset {}: expected {}, found end of input", self.offset, self.expected),
+        }
+    }
+}
+
+pub type ParseResult<'a, S, Output> = Result<(Output, Input<'a, S>), ParseError>;
+
+pub trait Parser<'a, S: Source + ?Sized + 'a, Output> {
+    fn parse(&self, input: Input<'a, S>) -> ParseResult<'a, S, Output>;
+
+    fn map<F, NewOutput>(self, f: F) -> BoxedParser<'a, S, NewOutput>
+        where Self: Sized + 'a,
+            Output: 'a,
+            NewOutput: 'a,
+            F: Fn(Output) -> NewOutput + 'a
+    {
+        BoxedParser::new(move |input: Input<'a, S>| {
+            self.parse(input).map(|(output, remaining_input)| (f(output), remaining_input))
+        })
+    }
+
+    // Picks the next parser from what this one produced.
+    fn and_then<F, P, NewOutput>(self, f: F) -> BoxedParser<'a, S, NewOutput>
+        where Self: Sized + 'a,
+            Output: 'a,
+            NewOutput: 'a,
+            P: Parser<'a, S, NewOutput> + 'a,
+            F: Fn(Output) -> P + 'a
+    {
+        BoxedParser::new(move |input: Input<'a, S>| {
+            let (output, remaining_input) = self.parse(input)?;
+            f(output).parse(remaining_input)
+        })
+    }
+
+    // Fails where this parser started when the conversion gives nothing, like a
+    // number that does not fit its type.
+    fn try_map<F, NewOutput>(self, f: F, expected: &'static str) -> BoxedParser<'a, S, NewOutput>
+        where Self: Sized + 'a,
+            Output: 'a,
+            NewOutput: 'a,
+            F: Fn(Output) -> Option<NewOutput> + 'a
+    {
+        BoxedParser::new(move |input: Input<'a, S>| {
+            let (output, remaining_input) = self.parse(input)?;
+            match f(output) {
+                Some(new_output) => Ok((new_output, remaining_input)),
+                None => Err(input.get_error(expected)),
+            }
+        })
+    }
+
+    // Replaces what a failed parser expected, unless the failure is committed and
+    // so already points at something more specific.
+    fn label(self, expected: &'static str) -> BoxedParser<'a, S, Output>
+        where Self: Sized + 'a,
+            Output: 'a
+    {
+        BoxedParser::new(move |input: Input<'a, S>| {
+            self.parse(input).map_err(|error| {
+                if error.is_committed {
+                    error
+                } else {
+                    ParseError {
+                        expected: expected.to_string(),
+                        ..error
+                    }
+                }
+            })
+        })
+    }
+}
+
+impl<'a, S, F, Output> Parser<'a, S, Output> for F
+where
+    S: Source + ?Sized + 'a,
+    F: Fn(Input<'a, S>) -> ParseResult<'a, S, Output>
+{
+    fn parse(&self, input: Input<'a, S>) -> ParseResult<'a, S, Output> {
+        self(input)
+    }
+}
+
+pub struct BoxedParser<'a, S: Source + ?Sized, Output> {
+    parser: Box<dyn Parser<'a, S, Output> + 'a>
+}
+
+impl<'a, S: Source + ?Sized + 'a, Output> BoxedParser<'a, S, Output> {
+    pub fn new<P>(p: P) -> Self
+        where P: Parser<'a, S, Output> + 'a
+    {
+        BoxedParser {
+            parser: Box::new(p)
+        }
+    }
+}
+
+impl<'a, S: Source + ?Sized + 'a, Output> Parser<'a, S, Output> for BoxedParser<'a, S, Output> {
+    fn parse(&self, input: Input<'a, S>) -> ParseResult<'a, S, Output> {
+        self.parser.parse(input)
+    }
+}
+
+pub fn parse_all<'a, S, P, Output>(parser: P, source: &'a S) -> Result<Output, ParseError>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, Output>
+{
+    let (output, remaining_input) = parser.parse(Input::new(source))?;
+    match remaining_input.peek() {
+        Some(_) => Err(remaining_input.get_error("end of input")),
+        None => Ok(output),
+    }
+}
+
+pub fn select<'a, S, F, Output>(f: F, expected: &'static str) -> BoxedParser<'a, S, Output>
+    where S: Source + ?Sized + 'a,
+        F: Fn(S::Item) -> Option<Output> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match input.next() {
+            Some((item, next_input)) => match f(item) {
+                Some(output) => Ok((output, next_input)),
+                None => Err(input.get_error(expected)),
+            },
+            None => Err(input.get_error(expected)),
+        }
+    })
+}
+
+pub fn satisfy<'a, S, F>(predicate: F, expected: &'static str) -> BoxedParser<'a, S, S::Item>
+    where S: Source + ?Sized + 'a,
+        F: Fn(S::Item) -> bool + 'a
+{
+    select(move |item| if predicate(item) { Some(item) } else { None }, expected)
+}
+
+pub fn match_item<'a, S>(expected_item: S::Item) -> BoxedParser<'a, S, ()>
+    where S: Source + ?Sized + 'a,
+        S::Item: PartialEq + 'a
+{
+    let expected = format!("{:?}", expected_item);
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match input.next() {
+            Some((item, next_input)) if item == expected_item => Ok(((), next_input)),
+            _ => Err(input.get_error(&expected)),
+        }
+    })
+}
+
+pub fn end<'a, S: Source + ?Sized + 'a>() -> BoxedParser<'a, S, ()> {
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match input.peek() {
+            Some(_) => Err(input.get_error("end of input")),
+            None => Ok(((), input)),
+        }
+    })
+}
+
+pub fn take_while<'a, S, F>(predicate: F) -> BoxedParser<'a, S, &'a S>
+    where S: Source + ?Sized + 'a,
+        F: Fn(S::Item) -> bool + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        let mut current_input = input;
+        while let Some((item, next_input)) = current_input.next() {
+            if !predicate(item) {
+                break;
+            }
+            current_input = next_input;
+        }
+        Ok((input.source.get_slice(input.offset, current_input.offset), current_input))
+    })
+}
+
+pub fn take_while1<'a, S, F>(predicate: F, expected: &'static str) -> BoxedParser<'a, S, &'a S>
+    where S: Source + ?Sized + 'a,
+        F: Fn(S::Item) -> bool + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match input.next() {
+            Some((item, _)) if predicate(item) => {},
+            _ => return Err(input.get_error(expected)),
+        }
+        let mut current_input = input;
+        while let Some((item, next_input)) = current_input.next() {
+            if !predicate(item) {
+                break;
+            }
+            current_input = next_input;
+        }
+        Ok((input.source.get_slice(input.offset, current_input.offset), current_input))
+    })
+}
+
+pub fn literal<'a>(expected: &'static str) -> BoxedParser<'a, str, ()> {
+    let label = format!("{:?}", expected);
+    BoxedParser::new(move |input: Input<'a, str>| {
+        if input.source[input.offset..].starts_with(expected) {
+            Ok(((), Input { source: input.source, offset: input.offset + expected.len() }))
+        } else {
+            Err(input.get_error(&label))
+        }
+    })
+}
+
+pub fn whitespace<'a>() -> BoxedParser<'a, str, &'a str> {
+    take_while(char::is_whitespace)
+}
+
+pub fn integer<'a>() -> BoxedParser<'a, str, i64> {
+    pair(
+        none_or_one(match_item::<str>('-')),
+        take_while1(|chr: char| chr.is_ascii_digit(), "a digit")
+    ).try_map(|(sign, digits)| {
+        let value = digits.parse::<i64>().ok()?;
+        Some(if sign.is_some() { -value } else { value })
+    }, "an integer")
+}
+
+pub fn pair<'a, S, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, S, (R1, R2)>
+    where S: Source + ?Sized + 'a,
+        P1: Parser<'a, S, R1> + 'a,
+        P2: Parser<'a, S, R2> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        let (first_output, first_input) = p1.parse(input)?;
+        let (second_output, second_input) = p2.parse(first_input)?;
+        Ok(((first_output, second_output), second_input))
+    })
+}
+
+pub fn left<'a, S, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, S, R1>
+    where S: Source + ?Sized + 'a,
+        P1: Parser<'a, S, R1> + 'a,
+        P2: Parser<'a, S, R2> + 'a,
+        R1: 'a,
+        R2: 'a
+{
+    pair(p1, p2).map(|(first_output, _)| first_output)
+}
+
+pub fn right<'a, S, P1, R1, P2, R2>(p1: P1, p2: P2) -> BoxedParser<'a, S, R2>
+    where S: Source + ?Sized + 'a,
+        P1: Parser<'a, S, R1> + 'a,
+        P2: Parser<'a, S, R2> + 'a,
+        R1: 'a,
+        R2: 'a
+{
+    pair(p1, p2).map(|(_, second_output)| second_output)
+}
+
+pub fn either<'a, S, P1, P2, R>(p1: P1, p2: P2) -> BoxedParser<'a, S, R>
+    where S: Source + ?Sized + 'a,
+        P1: Parser<'a, S, R> + 'a,
+        P2: Parser<'a, S, R> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match p1.parse(input) {
+            Err(first_error) if !first_error.is_committed => {
+                p2.parse(input).map_err(|second_error| {
+                    if second_error.is_committed {
+                        second_error
+                    } else {
+                        first_error.merge(second_error)
+                    }
+                })
+            },
+            result => result,
+        }
+    })
+}
+
+pub fn none_or_one<'a, S, P, R>(p: P) -> BoxedParser<'a, S, Option<R>>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match p.parse(input) {
+            Ok((output, next_input)) => Ok((Some(output), next_input)),
+            Err(error) if error.is_committed => Err(error),
+            Err(_) => Ok((None, input)),
+        }
+    })
+}
+
+// Stops at the first uncommitted failure, and also when an element matches without
+// consuming anything, which would otherwise repeat forever.
+fn repeat<'a, S, P, R>(p: &P, input: Input<'a, S>, outputs: &mut Vec<R>) -> Result<Input<'a, S>, ParseError>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R>
+{
+    let mut current_input = input;
+    loop {
+        match p.parse(current_input) {
+            Ok((output, next_input)) => {
+                let has_consumed = next_input.offset > current_input.offset;
+                outputs.push(output);
+                current_input = next_input;
+                if !has_consumed {
+                    return Ok(current_input);
+                }
+            },
+            Err(error) if error.is_committed => return Err(error),
+            Err(_) => return Ok(current_input),
+        }
+    }
+}
+
+pub fn many<'a, S, P, R>(p: P) -> BoxedParser<'a, S, Vec<R>>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        let mut outputs: Vec<R> = Vec::new();
+        let next_input = repeat(&p, input, &mut outputs)?;
+        Ok((outputs, next_input))
+    })
+}
+
+pub fn one_or_more<'a, S, P, R>(p: P) -> BoxedParser<'a, S, Vec<R>>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        let (first_output, first_input) = p.parse(input)?;
+        let mut outputs: Vec<R> = vec![first_output];
+        let next_input = repeat(&p, first_input, &mut outputs)?;
+        Ok((outputs, next_input))
+    })
+}
+
+// Elements may be empty, as long as separators keep the input moving.
+pub fn sep_by<'a, S, P, R, PS, RS>(p: P, separator: PS) -> BoxedParser<'a, S, Vec<R>>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a,
+        PS: Parser<'a, S, RS> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        let (first_output, mut current_input) = p.parse(input)?;
+        let mut outputs: Vec<R> = vec![first_output];
+        loop {
+            match separator.parse(current_input) {
+                Ok((_, separator_input)) => {
+                    let (output, next_input) = p.parse(separator_input)?;
+                    outputs.push(output);
+                    current_input = next_input;
+                },
+                Err(error) if error.is_committed => return Err(error),
+                Err(_) => break,
+            }
+        }
+        Ok((outputs, current_input))
+    })
+}
+
+// Once the input got this far, a failure is an error in the input rather than a
+// reason to try another alternative.
+pub fn cut<'a, S, P, R>(p: P) -> BoxedParser<'a, S, R>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        p.parse(input).map_err(|error| ParseError {
+            is_committed: true,
+            ..error
+        })
+    })
+}
+
+// Builds the parser when it is used, which is what lets grammars refer to
+// themselves.
+pub fn lazy<'a, S, F, P, R>(f: F) -> BoxedParser<'a, S, R>
+    where S: Source + ?Sized + 'a,
+        F: Fn() -> P + 'a,
+        P: Parser<'a, S, R>
+{
+    BoxedParser::new(move |input: Input<'a, S>| f().parse(input))
+}
+
+// On failure, skips past the next match of `sync` and hands the error over as
+// output, so one bad entry does not hide the ones after it. At the end of the input
+// there is nothing left to skip, so the failure stays a failure.
+pub fn recover<'a, S, P, R, PS, RS>(p: P, sync: PS) -> BoxedParser<'a, S, Result<R, ParseError>>
+    where S: Source + ?Sized + 'a,
+        P: Parser<'a, S, R> + 'a,
+        PS: Parser<'a, S, RS> + 'a
+{
+    BoxedParser::new(move |input: Input<'a, S>| {
+        match p.parse(input) {
+            Ok((output, next_input)) => Ok((Ok(output), next_input)),
+            Err(error) if input.peek().is_none() => Err(error),
+            Err(error) => {
+                let mut current_input = input;
+                loop {
+                    if let Ok((_, sync_input)) = sync.parse(current_input) {
+                        current_input = sync_input;
+                        break;
+                    }
+                    match current_input.next() {
+                        Some((_, next_input)) => current_input = next_input,
+                        None => break,
+                    }
+                }
+                Ok((Err(ParseError { is_committed: false, ..error }), current_input))
+            },
+        }
+    })
+}
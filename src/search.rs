@@ -0,0 +1,198 @@
+use std::{cmp::Reverse, collections::{HashMap, HashSet, VecDeque, hash_map::Entry}, hash::Hash};
+
+use priority_queue::PriorityQueue;
+
+// A graph given by how to leave each state. Neighbours come with the cost of the
+// move, which breadth-first searches ignore, and are tried in the order given, so
+// the order decides between equally good parents.
+pub trait StateSpace {
+    type State: Clone + Eq + Hash;
+
+    fn get_neighbors(&mut self, state: &Self::State) -> Vec<(Self::State, usize)>;
+
+    // A lower bound on the cost left to a goal. Zero turns A* into Dijkstra.
+    fn get_heuristic(&mut self, _state: &Self::State) -> usize {
+        0
+    }
+}
+
+pub struct SearchTree<S> {
+    pub distances: HashMap<S, usize>,
+    pub parents: HashMap<S, S>,
+    pub goal: Option<S>,
+    pub num_expanded: usize
+}
+
+impl<S: Clone + Eq + Hash> SearchTree<S> {
+    fn new(start: &S) -> SearchTree<S> {
+        let mut distances: HashMap<S, usize> = HashMap::new();
+        distances.insert(start.clone(), 0);
+        SearchTree {
+            distances,
+            parents: HashMap::new(),
+            goal: None,
+            num_expanded: 0
+        }
+    }
+
+    // The states from the start to `state`, both included.
+    pub fn get_path(&self, state: &S) -> Option<Vec<S>> {
+        self.distances.get(state)?;
+        let mut path: Vec<S> = vec![state.clone()];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn get_goal_path(&self) -> Option<Vec<S>> {
+        self.get_path(self.goal.as_ref()?)
+    }
+}
+
+// Counts moves rather than costs, and visits everything reachable unless a goal
+// is found first.
+pub fn bfs<P, G>(space: &mut P, start: P::State, is_goal: G) -> SearchTree<P::State>
+    where P: StateSpace,
+        G: Fn(&P::State) -> bool
+{
+    let mut tree = SearchTree::new(&start);
+    let mut queue: VecDeque<P::State> = VecDeque::new();
+    queue.push_back(start);
+    while let Some(state) = queue.pop_front() {
+        if is_goal(&state) {
+            tree.goal = Some(state);
+            return tree;
+        }
+        tree.num_expanded += 1;
+        let distance = tree.distances[&state];
+        for (next_state, _) in space.get_neighbors(&state) {
+            if let Entry::Vacant(entry) = tree.distances.entry(next_state.clone()) {
+                entry.insert(distance + 1);
+                tree.parents.insert(next_state.clone(), state.clone());
+                queue.push_back(next_state);
+            }
+        }
+    }
+    tree
+}
+
+fn search_by_cost<P, G>(space: &mut P, start: P::State, is_goal: G, use_heuristic: bool) -> SearchTree<P::State>
+    where P: StateSpace,
+        G: Fn(&P::State) -> bool
+{
+    let mut tree = SearchTree::new(&start);
+    let mut expanded: HashSet<P::State> = HashSet::new();
+    let mut frontier: PriorityQueue<P::State, Reverse<usize>> = PriorityQueue::new();
+    frontier.push(start, Reverse(0));
+    while let Some((state, _)) = frontier.pop() {
+        if is_goal(&state) {
+            tree.goal = Some(state);
+            return tree;
+        }
+        tree.num_expanded += 1;
+        let distance = tree.distances[&state];
+        for (next_state, cost) in space.get_neighbors(&state) {
+            let next_distance = distance + cost;
+            if expanded.contains(&next_state) || tree.distances.get(&next_state).is_some_and(|&known_distance| known_distance <= next_distance) {
+                continue;
+            }
+            let priority = if use_heuristic { next_distance + space.get_heuristic(&next_state) } else { next_distance };
+            tree.distances.insert(next_state.clone(), next_distance);
+            tree.parents.insert(next_state.clone(), state.clone());
+            frontier.push(next_state, Reverse(priority));
+        }
+        expanded.insert(state);
+    }
+    tree
+}
+
+pub fn dijkstra<P, G>(space: &mut P, start: P::State, is_goal: G) -> SearchTree<P::State>
+    where P: StateSpace,
+        G: Fn(&P::State) -> bool
+{
+    search_by_cost(space, start, is_goal, false)
+}
+
+// Needs a heuristic that never overestimates and never drops by more than the cost
+// of a move, since expanded states are not reopened.
+pub fn a_star<P, G>(space: &mut P, start: P::State, is_goal: G) -> SearchTree<P::State>
+    where P: StateSpace,
+        G: Fn(&P::State) -> bool
+{
+    search_by_cost(space, start, is_goal, true)
+}
+
+// Grows one breadth-first layer at a time from both ends, always the smaller one,
+// until they touch. Only valid when every move can be taken back.
+pub fn bidirectional_bfs<P: StateSpace>(space: &mut P, start: P::State, goal: P::State) -> Option<Vec<P::State>> {
+    let mut forward_tree = SearchTree::new(&start);
+    let mut backward_tree = SearchTree::new(&goal);
+    let mut forward_layer: Vec<P::State> = vec![start];
+    let mut backward_layer: Vec<P::State> = vec![goal];
+    let mut meeting_state: Option<P::State> = forward_layer.iter()
+        .find(|&state| backward_tree.distances.contains_key(state))
+        .cloned();
+    while meeting_state.is_none() && !forward_layer.is_empty() && !backward_layer.is_empty() {
+        let is_forward = forward_layer.len() <= backward_layer.len();
+        let (tree, other_tree, layer) = if is_forward {
+            (&mut forward_tree, &backward_tree, &mut forward_layer)
+        } else {
+            (&mut backward_tree, &forward_tree, &mut backward_layer)
+        };
+        let mut next_layer: Vec<P::State> = Vec::new();
+        for state in layer.iter() {
+            let distance = tree.distances[state];
+            for (next_state, _) in space.get_neighbors(state) {
+                if let Entry::Vacant(entry) = tree.distances.entry(next_state.clone()) {
+                    entry.insert(distance + 1);
+                    tree.parents.insert(next_state.clone(), state.clone());
+                    if meeting_state.is_none() && other_tree.distances.contains_key(&next_state) {
+                        meeting_state = Some(next_state.clone());
+                    }
+                    next_layer.push(next_state);
+                }
+            }
+        }
+        *layer = next_layer;
+    }
+    let meeting_state = meeting_state?;
+    let mut path = forward_tree.get_path(&meeting_state)?;
+    let mut backward_path = backward_tree.get_path(&meeting_state)?;
+    backward_path.pop();
+    path.extend(backward_path.into_iter().rev());
+    Some(path)
+}
+
+// Always expands the state with the highest priority and stops at the first goal
+// taken off the queue. Costs are ignored, which suits searches over regions that
+// are ranked by an estimate of what they contain.
+pub fn best_first<P, F, O, G>(space: &mut P, start: P::State, get_priority: F, is_goal: G) -> SearchTree<P::State>
+    where P: StateSpace,
+        F: Fn(&P::State) -> O,
+        O: Ord,
+        G: Fn(&P::State) -> bool
+{
+    let mut tree = SearchTree::new(&start);
+    let mut frontier: PriorityQueue<P::State, O> = PriorityQueue::new();
+    let start_priority = get_priority(&start);
+    frontier.push(start, start_priority);
+    while let Some((state, _)) = frontier.pop() {
+        if is_goal(&state) {
+            tree.goal = Some(state);
+            return tree;
+        }
+        tree.num_expanded += 1;
+        let distance = tree.distances[&state];
+        for (next_state, _) in space.get_neighbors(&state) {
+            if let Entry::Vacant(entry) = tree.distances.entry(next_state.clone()) {
+                entry.insert(distance + 1);
+                tree.parents.insert(next_state.clone(), state.clone());
+                let priority = get_priority(&next_state);
+                frontier.push(next_state, priority);
+            }
+        }
+    }
+    tree
+}
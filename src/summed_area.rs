@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+use crate::day_three::Matrix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+    pub sum: i64
+}
+
+impl Region {
+    // Puzzles number grid cells from 1, with X as the column.
+    pub fn get_coordinates(&self) -> (usize, usize) {
+        (self.col + 1, self.row + 1)
+    }
+
+    pub fn overlaps(&self, other: &Region) -> bool {
+        self.row < other.row + other.rows && other.row < self.row + self.rows &&
+            self.col < other.col + other.cols && other.col < self.col + self.cols
+    }
+}
+
+// Prefix sums with an extra leading row and column of zeros, so that the sum of
+// any rectangle is read from four entries.
+pub struct SummedAreaTable {
+    sums: Matrix<i64>,
+    pub rows: usize,
+    pub cols: usize
+}
+
+impl SummedAreaTable {
+    pub fn new<T: Clone + Eq + Into<i64>>(matrix: &Matrix<T>) -> SummedAreaTable {
+        let mut sums: Matrix<i64> = Matrix::new(matrix.rows + 1, matrix.cols + 1, 0);
+        for row in 0..matrix.rows {
+            for col in 0..matrix.cols {
+                let sum = matrix.get(row, col).into() + sums.get(row, col + 1) + sums.get(row + 1, col) - sums.get(row, col);
+                sums.set(row + 1, col + 1, sum);
+            }
+        }
+        SummedAreaTable {
+            sums,
+            rows: matrix.rows,
+            cols: matrix.cols
+        }
+    }
+
+    pub fn get_sum(&self, row: usize, col: usize, rows: usize, cols: usize) -> i64 {
+        self.sums.get(row + rows, col + cols) - self.sums.get(row, col + cols) -
+            self.sums.get(row + rows, col) + self.sums.get(row, col)
+    }
+
+    pub fn get_region(&self, row: usize, col: usize, rows: usize, cols: usize) -> Region {
+        Region {
+            row,
+            col,
+            rows,
+            cols,
+            sum: self.get_sum(row, col, rows, cols)
+        }
+    }
+
+    // Ties go to the first square in reading order.
+    pub fn get_best_square(&self, size: usize) -> Option<Region> {
+        if size == 0 || size > self.rows || size > self.cols {
+            return None;
+        }
+        let mut best_region: Option<Region> = None;
+        for row in 0..=(self.rows - size) {
+            for col in 0..=(self.cols - size) {
+                let region = self.get_region(row, col, size, size);
+                if best_region.is_none_or(|best_region| region.sum > best_region.sum) {
+                    best_region = Some(region);
+                }
+            }
+        }
+        best_region
+    }
+
+    // Ties go to the smallest square, then to the first one in reading order.
+    pub fn get_best_square_of_any_size(&self) -> Option<Region> {
+        let mut best_region: Option<Region> = None;
+        for size in 1..=self.rows.min(self.cols) {
+            if let Some(region) = self.get_best_square(size) {
+                if best_region.is_none_or(|best_region| region.sum > best_region.sum) {
+                    best_region = Some(region);
+                }
+            }
+        }
+        best_region
+    }
+
+    // Kadane over column prefix sums for every band of rows, where a monotonic queue
+    // keeps the smallest prefix among the starts that give an allowed width.
+    pub fn get_best_rectangle(&self, rows: RangeInclusive<usize>, cols: RangeInclusive<usize>) -> Option<Region> {
+        let min_rows = (*rows.start()).max(1);
+        let max_rows = (*rows.end()).min(self.rows);
+        let min_cols = (*cols.start()).max(1);
+        let max_cols = (*cols.end()).min(self.cols);
+        if min_rows > max_rows || min_cols > max_cols {
+            return None;
+        }
+        let mut best_region: Option<Region> = None;
+        for height in min_rows..=max_rows {
+            for row in 0..=(self.rows - height) {
+                let prefixes: Vec<i64> = (0..=self.cols).map(|col| self.get_sum(row, 0, height, col)).collect();
+                let mut starts: VecDeque<usize> = VecDeque::new();
+                for end in min_cols..=self.cols {
+                    let start = end - min_cols;
+                    while starts.back().is_some_and(|&back| prefixes[back] > prefixes[start]) {
+                        starts.pop_back();
+                    }
+                    starts.push_back(start);
+                    while starts.front().is_some_and(|&front| front + max_cols < end) {
+                        starts.pop_front();
+                    }
+                    let start = *starts.front().unwrap();
+                    let sum = prefixes[end] - prefixes[start];
+                    if best_region.is_none_or(|best_region| sum > best_region.sum) {
+                        best_region = Some(Region {
+                            row,
+                            col: start,
+                            rows: height,
+                            cols: end - start,
+                            sum
+                        });
+                    }
+                }
+            }
+        }
+        best_region
+    }
+
+    // Greedy: each pick is the best square that does not overlap an earlier pick.
+    pub fn get_top_squares(&self, sizes: RangeInclusive<usize>, count: usize) -> Vec<Region> {
+        let mut regions: Vec<Region> = Vec::new();
+        while regions.len() < count {
+            let mut best_region: Option<Region> = None;
+            for size in sizes.clone() {
+                if size == 0 || size > self.rows || size > self.cols {
+                    continue;
+                }
+                for row in 0..=(self.rows - size) {
+                    for col in 0..=(self.cols - size) {
+                        let region = self.get_region(row, col, size, size);
+                        if regions.iter().any(|other| other.overlaps(&region)) {
+                            continue;
+                        }
+                        if best_region.is_none_or(|best_region| region.sum > best_region.sum) {
+                            best_region = Some(region);
+                        }
+                    }
+                }
+            }
+            match best_region {
+                Some(region) => regions.push(region),
+                None => break
+            }
+        }
+        regions
+    }
+}
@@ -1,4 +1,4 @@
-use std::fs;
+use std::{collections::{HashMap, hash_map::DefaultHasher}, fs, hash::{Hash, Hasher}};
 
 use crate::day_three::Matrix;
 
@@ -18,4 +18,66 @@ pub fn read_matrix(path: &str) -> Matrix<char> {
         }
     }
     matrix
+}
+
+// Xorshift step; the state must never be zero.
+pub fn get_next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub prefix_length: usize,
+    pub period: usize
+}
+
+fn get_hash<S: Hash>(state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Iterates `step` from `initial` until a state repeats or `max_steps` steps are
+// done, and returns the states seen before the repeat. Previous states are indexed
+// by their hash, so each step costs a single lookup instead of a scan of the whole
+// history.
+pub fn iterate_until_cycle<S, F>(initial: S, max_steps: usize, mut step: F) -> (Vec<S>, Option<Cycle>)
+    where S: Hash + Eq,
+          F: FnMut(&S) -> S
+{
+    let mut indices: HashMap<u64, Vec<usize>> = HashMap::new();
+    indices.insert(get_hash(&initial), vec![0]);
+    let mut history: Vec<S> = vec![initial];
+    while history.len() <= max_steps {
+        let next_state = step(history.last().unwrap());
+        let hash = get_hash(&next_state);
+        let same_hash_indices = indices.entry(hash).or_default();
+        if let Some(&index) = same_hash_indices.iter().find(|&&index| history[index] == next_state) {
+            let cycle = Cycle {
+                prefix_length: index,
+                period: history.len() - index
+            };
+            return (history, Some(cycle));
+        }
+        same_hash_indices.push(history.len());
+        history.push(next_state);
+    }
+    (history, None)
+}
+
+pub fn get_state_after_steps<S, F>(initial: S, num_steps: usize, step: F) -> S
+    where S: Hash + Eq,
+          F: FnMut(&S) -> S
+{
+    let (mut history, cycle) = iterate_until_cycle(initial, num_steps, step);
+    let index = match cycle {
+        Some(cycle) if num_steps >= cycle.prefix_length => {
+            cycle.prefix_length + (num_steps - cycle.prefix_length) % cycle.period
+        },
+        _ => num_steps,
+    };
+    history.swap_remove(index)
 }
\ No newline at end of file